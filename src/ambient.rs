@@ -0,0 +1,469 @@
+//! Time-driven lighting effects for when the keyboard is idle.
+//!
+//! All effects ignore key events and redraw the board every frame. Their speed is given as the
+//! period of one full cycle, their brightness from 0 to 1.
+
+use std::f64::consts::PI;
+use std::time::{Duration, Instant};
+use libusb::Result as UsbResult;
+use rand::{self, Rng, SeedableRng, XorShiftRng};
+use g910::*;
+
+use backend::{self, secs, KeyboardHandler, Leds};
+use geometry::{Geometry, Variant};
+use gradient::from_hsv;
+
+/// Milliseconds between two frames.
+const FRAME_INTERVAL: u64 = 40;
+
+/// Returns the color dimmed to the given brightness.
+fn dim(color: Color, brightness: f64) -> Color {
+    let brightness = brightness.max(0.0).min(1.0);
+    Color::new((color.red as f64 * brightness).round() as u8,
+        (color.green as f64 * brightness).round() as u8,
+        (color.blue as f64 * brightness).round() as u8)
+}
+
+/// Returns the time since the first frame, starting the clock if it hasn't been yet.
+fn elapsed(start: &mut Option<Instant>, now: Instant) -> f64 {
+    secs(now.duration_since(*start.get_or_insert(now)))
+}
+
+/// Fades the whole board in and out in a single color.
+pub struct Breathing {
+    color: Color,
+    period: Duration,
+    brightness: f64,
+    start: Option<Instant>,
+}
+
+impl Breathing {
+    /// Creates a white breathing effect taking 4 seconds per breath.
+    pub fn new() -> Breathing {
+        Breathing {
+            color: Color::new(255, 255, 255),
+            period: Duration::from_secs(4),
+            brightness: 1.0,
+            start: None,
+        }
+    }
+
+    pub fn color(mut self, color: Color) -> Breathing {
+        self.color = color;
+        self
+    }
+
+    /// Sets the duration of one breath.
+    pub fn period(mut self, period: Duration) -> Breathing {
+        self.period = period;
+        self
+    }
+
+    /// Sets the brightness at the peak of a breath.
+    pub fn brightness(mut self, brightness: f64) -> Breathing {
+        self.brightness = brightness;
+        self
+    }
+}
+
+impl KeyboardHandler for Breathing {
+    #[allow(unused_variables)]
+    fn accept_key(&self, evt: &KeyEvent) -> bool {
+        false
+    }
+
+    #[allow(unused_variables)]
+    fn handle_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        Ok(())
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(FRAME_INTERVAL))
+    }
+
+    fn handle_time(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        let t = elapsed(&mut self.start, now) / secs(self.period);
+        let level = 0.5 - 0.5 * (2.0 * PI * t).cos();
+        keyboard.set_all_colors(dim(self.color, level * self.brightness))
+    }
+}
+
+/// Cycles the whole board through all hues.
+pub struct ColorCycle {
+    period: Duration,
+    saturation: f64,
+    brightness: f64,
+    start: Option<Instant>,
+}
+
+impl ColorCycle {
+    /// Creates a color cycle taking 10 seconds through all hues.
+    pub fn new() -> ColorCycle {
+        ColorCycle {
+            period: Duration::from_secs(10),
+            saturation: 1.0,
+            brightness: 1.0,
+            start: None,
+        }
+    }
+
+    /// Sets the duration of one cycle through all hues.
+    pub fn period(mut self, period: Duration) -> ColorCycle {
+        self.period = period;
+        self
+    }
+
+    /// Sets the saturation of the colors, lower values giving pastel colors.
+    pub fn saturation(mut self, saturation: f64) -> ColorCycle {
+        self.saturation = saturation;
+        self
+    }
+
+    pub fn brightness(mut self, brightness: f64) -> ColorCycle {
+        self.brightness = brightness;
+        self
+    }
+}
+
+impl KeyboardHandler for ColorCycle {
+    #[allow(unused_variables)]
+    fn accept_key(&self, evt: &KeyEvent) -> bool {
+        false
+    }
+
+    #[allow(unused_variables)]
+    fn handle_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        Ok(())
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(FRAME_INTERVAL))
+    }
+
+    fn handle_time(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        let t = elapsed(&mut self.start, now) / secs(self.period);
+        keyboard.set_all_colors(from_hsv(360.0 * t, self.saturation, self.brightness))
+    }
+}
+
+/// The direction a `RainbowWave` travels in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    /// From left to right.
+    Horizontal,
+    /// From the top left to the bottom right.
+    Diagonal,
+}
+
+/// Lets a rainbow travel across the board, positioning keys by their physical location.
+pub struct RainbowWave {
+    keys: Vec<(Key, (f64, f64))>,
+    direction: Direction,
+    period: Duration,
+    /// Distance in millimetres covered by all hues.
+    wavelength: f64,
+    brightness: f64,
+    start: Option<Instant>,
+}
+
+impl RainbowWave {
+    /// Creates a horizontal wave spanning the board and passing a key every 3 seconds.
+    pub fn new() -> RainbowWave {
+        RainbowWave::with_geometry(Geometry::new(Variant::Iso))
+    }
+
+    /// Creates a wave positioning keys by the given geometry, e.g. of an ANSI keyboard.
+    pub fn with_geometry(geometry: Geometry) -> RainbowWave {
+        RainbowWave {
+            keys: geometry.lightable(),
+            direction: Direction::Horizontal,
+            period: Duration::from_secs(3),
+            wavelength: geometry.size().0,
+            brightness: 1.0,
+            start: None,
+        }
+    }
+
+    pub fn direction(mut self, direction: Direction) -> RainbowWave {
+        self.direction = direction;
+        self
+    }
+
+    /// Sets the time a key takes to go through all hues.
+    pub fn period(mut self, period: Duration) -> RainbowWave {
+        self.period = period;
+        self
+    }
+
+    /// Sets the distance in millimetres between two keys of the same hue.
+    pub fn wavelength(mut self, wavelength: f64) -> RainbowWave {
+        self.wavelength = wavelength;
+        self
+    }
+
+    pub fn brightness(mut self, brightness: f64) -> RainbowWave {
+        self.brightness = brightness;
+        self
+    }
+}
+
+impl KeyboardHandler for RainbowWave {
+    #[allow(unused_variables)]
+    fn accept_key(&self, evt: &KeyEvent) -> bool {
+        false
+    }
+
+    #[allow(unused_variables)]
+    fn handle_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        Ok(())
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(FRAME_INTERVAL))
+    }
+
+    fn handle_time(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        let t = elapsed(&mut self.start, now) / secs(self.period);
+        let key_colors = self.keys.iter()
+            .map(|&(ref key, (x, y))| {
+                let distance = match self.direction {
+                    Direction::Horizontal => x,
+                    Direction::Diagonal => (x + y) / 2.0f64.sqrt(),
+                };
+                let hue = 360.0 * (t - distance / self.wavelength);
+                KeyColor::new(key.clone(), from_hsv(hue, 1.0, self.brightness))
+            })
+            .collect();
+        keyboard.set_key_colors(key_colors)
+    }
+}
+
+/// Lets random keys twinkle like stars on a dark sky.
+pub struct Starlight {
+    keys: Vec<Key>,
+    color: Color,
+    background: Color,
+    /// New stars per second.
+    rate: f64,
+    twinkle: Duration,
+    brightness: f64,
+    /// Every twinkling key and when it started.
+    stars: Vec<(Key, Instant)>,
+    /// The stars which were lit in the last frame, to turn them off.
+    lit: Vec<Key>,
+    last: Option<Instant>,
+    rng: XorShiftRng,
+}
+
+impl Starlight {
+    /// Creates white stars on black, 5 per second, each twinkling for a second.
+    pub fn new() -> Starlight {
+        Starlight::with_geometry(Geometry::new(Variant::Iso))
+    }
+
+    /// Creates stars on the keys of the given geometry, e.g. of an ANSI keyboard.
+    pub fn with_geometry(geometry: Geometry) -> Starlight {
+        Starlight::with_rng(&geometry, rand::thread_rng().gen())
+    }
+
+    /// Creates a starlight effect with its stars determined by the seed, e.g. for golden-frame
+    /// snapshots.
+    ///
+    /// The seed must not be all zeroes.
+    pub fn with_seed(seed: [u32; 4]) -> Starlight {
+        Starlight::with_rng(&Geometry::new(Variant::Iso), XorShiftRng::from_seed(seed))
+    }
+
+    fn with_rng(geometry: &Geometry, rng: XorShiftRng) -> Starlight {
+        Starlight {
+            keys: geometry.lightable().into_iter().map(|(key, _)| key).collect(),
+            color: Color::new(255, 255, 255),
+            background: Color::new(0, 0, 0),
+            rate: 5.0,
+            twinkle: Duration::from_secs(1),
+            brightness: 1.0,
+            stars: Vec::new(),
+            lit: Vec::new(),
+            last: None,
+            rng: rng,
+        }
+    }
+
+    pub fn color(mut self, color: Color) -> Starlight {
+        self.color = color;
+        self
+    }
+
+    pub fn background(mut self, background: Color) -> Starlight {
+        self.background = background;
+        self
+    }
+
+    /// Sets how many stars appear per second.
+    pub fn rate(mut self, rate: f64) -> Starlight {
+        self.rate = rate;
+        self
+    }
+
+    /// Sets how long a star takes to fade in and out again.
+    pub fn twinkle(mut self, twinkle: Duration) -> Starlight {
+        self.twinkle = twinkle;
+        self
+    }
+
+    pub fn brightness(mut self, brightness: f64) -> Starlight {
+        self.brightness = brightness;
+        self
+    }
+}
+
+impl KeyboardHandler for Starlight {
+    fn init(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        keyboard.set_all_colors(self.background)
+    }
+
+    #[allow(unused_variables)]
+    fn accept_key(&self, evt: &KeyEvent) -> bool {
+        false
+    }
+
+    #[allow(unused_variables)]
+    fn handle_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        Ok(())
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(FRAME_INTERVAL))
+    }
+
+    fn handle_time(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        let frame = secs(now.duration_since(*self.last.get_or_insert(now)));
+        self.last = Some(now);
+        // each frame spawns a star with the probability matching the rate
+        let mut expected = self.rate * frame;
+        while expected > 0.0 && !self.keys.is_empty() {
+            if self.rng.gen::<f64>() < expected {
+                let key = self.keys[self.rng.gen::<usize>() % self.keys.len()].clone();
+                if !self.stars.iter().any(|&(ref k, _)| *k == key) {
+                    self.stars.push((key, now));
+                }
+            }
+            expected -= 1.0;
+        }
+        let twinkle = self.twinkle;
+        self.stars.retain(|&(_, start)| now.duration_since(start) < twinkle);
+
+        let mut key_colors: Vec<_> = self.lit.iter()
+            .filter(|key| !self.stars.iter().any(|&(ref k, _)| k == *key))
+            .map(|key| KeyColor::new(key.clone(), self.background))
+            .collect();
+        for &(ref key, start) in &self.stars {
+            // rises and falls like a triangle over the twinkle
+            let t = secs(now.duration_since(start)) / secs(twinkle);
+            let level = 1.0 - (2.0 * t - 1.0).abs();
+            let color = Color::new(
+                blend(self.background.red, self.color.red, level * self.brightness),
+                blend(self.background.green, self.color.green, level * self.brightness),
+                blend(self.background.blue, self.color.blue, level * self.brightness));
+            key_colors.push(KeyColor::new(key.clone(), color));
+        }
+        self.lit = self.stars.iter().map(|&(ref k, _)| k.clone()).collect();
+        if key_colors.is_empty() {
+            return Ok(());
+        }
+        keyboard.set_key_colors(key_colors)
+    }
+}
+
+fn blend(from: u8, to: u8, t: f64) -> u8 {
+    (from as f64 + (to as f64 - from as f64) * t.max(0.0).min(1.0)).round() as u8
+}
+
+impl From<Breathing> for Handler {
+    fn from(handler: Breathing) -> Handler {
+        backend::into_handler(handler)
+    }
+}
+
+impl From<ColorCycle> for Handler {
+    fn from(handler: ColorCycle) -> Handler {
+        backend::into_handler(handler)
+    }
+}
+
+impl From<RainbowWave> for Handler {
+    fn from(handler: RainbowWave) -> Handler {
+        backend::into_handler(handler)
+    }
+}
+
+impl From<Starlight> for Handler {
+    fn from(handler: Starlight) -> Handler {
+        backend::into_handler(handler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use g910::*;
+
+    use backend::KeyboardHandler;
+    use geometry::{Geometry, Variant};
+    use mock::Harness;
+    use super::{Breathing, ColorCycle, RainbowWave, Starlight};
+
+    fn all_lit<H: KeyboardHandler>(harness: &Harness<H>, color: Color) -> bool {
+        harness.keyboard().frame().values().all(|&c| c == color)
+    }
+
+    #[test]
+    fn breathing_peaks_at_half_period() {
+        let mut harness = Harness::new(Breathing::new().color(Color::new(255, 0, 0))
+            .period(Duration::from_millis(400)));
+        harness.tick().unwrap();
+        assert!(all_lit(&harness, Color::new(0, 0, 0)));
+        harness.advance(Duration::from_millis(200)).unwrap();
+        assert!(all_lit(&harness, Color::new(255, 0, 0)));
+    }
+
+    #[test]
+    fn color_cycle_turns_hue() {
+        let mut harness = Harness::new(ColorCycle::new().period(Duration::from_millis(1200)));
+        harness.tick().unwrap();
+        assert!(all_lit(&harness, Color::new(255, 0, 0)));
+        harness.advance(Duration::from_millis(400)).unwrap();
+        assert!(all_lit(&harness, Color::new(0, 255, 0)));
+    }
+
+    #[test]
+    fn rainbow_wave_colors_by_position() {
+        let mut harness = Harness::new(RainbowWave::new());
+        harness.tick().unwrap();
+        let left = harness.keyboard().color(&Key::Standard(StandardKey::A)).unwrap();
+        let right = harness.keyboard().color(&Key::Standard(StandardKey::L)).unwrap();
+        assert!(left != right);
+        assert!(left != Color::new(0, 0, 0) && right != Color::new(0, 0, 0));
+    }
+
+    #[test]
+    fn starlight_is_seeded() {
+        let run = || {
+            let mut harness = Harness::new(Starlight::with_seed([1, 2, 3, 4]).rate(20.0));
+            harness.init().unwrap();
+            harness.advance(Duration::from_millis(500)).unwrap();
+            harness.keyboard().frame().clone()
+        };
+        let frame = run();
+        assert!(frame.values().any(|&c| c != Color::new(0, 0, 0)));
+        assert_eq!(frame, run());
+    }
+
+    #[test]
+    fn starlight_twinkles_on_keys_of_geometry() {
+        let starlight = Starlight::with_geometry(Geometry::new(Variant::Ansi));
+        assert!(!starlight.keys.contains(&Key::Standard(StandardKey::SmallerThan)));
+        assert!(starlight.keys.contains(&Key::Standard(StandardKey::Pipe)));
+        assert!(!starlight.keys.contains(&Key::Media(MediaKey::Mute)));
+    }
+}
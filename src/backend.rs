@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use libusb::Result as UsbResult;
+use g910::*;
+
+/// Something keys can be lit on.
+///
+/// Implemented for the `Keyboard` passed to handlers by `into_handler`, and by
+/// `mock::MockKeyboard`, which records the colors into a virtual frame instead of sending them
+/// over USB.
+pub trait Leds {
+    fn set_all_colors(&mut self, color: Color) -> UsbResult<()>;
+    fn set_key_colors(&mut self, key_colors: Vec<KeyColor>) -> UsbResult<()>;
+
+    /// Returns the color the key has been set to last, if known.
+    ///
+    /// Handlers lighting keys on top of others use it to restore them afterwards. On a real
+    /// keyboard only the colors set through the same `Handler` are known, so such handlers are
+    /// best combined with the ones they draw over in a `Profile`.
+    #[allow(unused_variables)]
+    fn color(&self, key: &Key) -> Option<Color> {
+        None
+    }
+}
+
+/// The colors last set on the keyboard, as they can't be read back from it.
+struct Frame {
+    all: Option<Color>,
+    keys: HashMap<Key, Color>,
+}
+
+/// A keyboard recording the colors set on it into the frame of the `Handler` using it, so
+/// `color` knows them.
+struct FrameLeds<'k, 'a: 'k> {
+    keyboard: &'k mut Keyboard<'a>,
+    frame: &'k mut Frame,
+}
+
+impl<'k, 'a> Leds for FrameLeds<'k, 'a> {
+    fn set_all_colors(&mut self, color: Color) -> UsbResult<()> {
+        try!(self.keyboard.set_all_colors(color));
+        self.frame.all = Some(color);
+        self.frame.keys.clear();
+        Ok(())
+    }
+
+    fn set_key_colors(&mut self, key_colors: Vec<KeyColor>) -> UsbResult<()> {
+        let colors: Vec<_> = key_colors.iter().map(|kc| (kc.key.clone(), kc.color)).collect();
+        try!(self.keyboard.set_key_colors(key_colors));
+        self.frame.keys.extend(colors);
+        Ok(())
+    }
+
+    fn color(&self, key: &Key) -> Option<Color> {
+        self.frame.keys.get(key).cloned().or(self.frame.all)
+    }
+}
+
+/// A handler with the frame of the keyboard it draws on.
+struct Framed<T> {
+    handler: T,
+    frame: Frame,
+}
+
+/// The callbacks of a handler, independent of the keyboard they are called with.
+///
+/// Every handler of this crate implements this trait. It is turned into a `Handler` for a real
+/// keyboard with `into_handler`, or driven by a `mock::Harness` without any hardware.
+pub trait KeyboardHandler {
+    #[allow(unused_variables)]
+    fn init(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        Ok(())
+    }
+
+    /// Redraws the current state after another handler has drawn over it, e.g. when switching
+    /// back to its profile. The keyboard has been turned off before.
+    #[allow(unused_variables)]
+    fn redraw(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        Ok(())
+    }
+
+    fn accept_key(&self, evt: &KeyEvent) -> bool;
+
+    /// Handles an accepted key event, which happened at `now`.
+    fn handle_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()>;
+
+    /// Returns the interval in which `handle_time` is called, or None if it isn't needed.
+    fn interval(&self) -> Option<Duration> {
+        None
+    }
+
+    #[allow(unused_variables)]
+    fn handle_time(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        Ok(())
+    }
+
+    /// Stops anything still in progress because the handler won't get events or time for a
+    /// while, e.g. when switching away from its profile. Handlers emitting keys release them.
+    fn suspend(&mut self) -> UsbResult<()> {
+        Ok(())
+    }
+}
+
+/// Builds a `Handler` calling the given handler's callbacks with the real keyboard and time.
+pub fn into_handler<T: KeyboardHandler + 'static>(handler: T) -> Handler {
+    let interval = handler.interval();
+    let framed = Framed {
+        handler: handler,
+        frame: Frame { all: None, keys: HashMap::new() },
+    };
+    let builder = HandlerBuilder::new(framed)
+        .init_fn(|framed, keyboard| {
+            let Framed { ref mut handler, ref mut frame } = *framed;
+            handler.init(&mut FrameLeds { keyboard: keyboard, frame: frame })
+        })
+        .accept_key_fn(|framed, evt| framed.handler.accept_key(evt))
+        .handle_key_fn(|framed, evt, keyboard| {
+            let Framed { ref mut handler, ref mut frame } = *framed;
+            handler.handle_key(evt, Instant::now(), &mut FrameLeds { keyboard: keyboard, frame: frame })
+        });
+    match interval {
+        Some(interval) => builder
+            .handle_time_fn(|framed, _, keyboard| {
+                let Framed { ref mut handler, ref mut frame } = *framed;
+                handler.handle_time(Instant::now(), &mut FrameLeds { keyboard: keyboard, frame: frame })
+            }, interval)
+            .build(),
+        None => builder.build(),
+    }
+}
+
+/// Returns the duration in seconds.
+pub(crate) fn secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0
+}
@@ -0,0 +1,70 @@
+//! Exports counts saved by a `HeatmapHandler` as csv, json or svg.
+//!
+//! Usage: heatmap-export <counts-file> <csv|json|svg> [output-file]
+
+extern crate g910_handler;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::process;
+use g910_handler::Heatmap;
+
+fn usage() -> ! {
+    eprintln!("usage: heatmap-export <counts-file> <csv|json|svg> [output-file]");
+    process::exit(2);
+}
+
+enum Format {
+    Csv,
+    Json,
+    Svg,
+}
+
+fn parse_format(format: &str) -> Option<Format> {
+    match format {
+        "csv" => Some(Format::Csv),
+        "json" => Some(Format::Json),
+        "svg" => Some(Format::Svg),
+        _ => None,
+    }
+}
+
+fn export<W: Write>(heatmap: &Heatmap, format: &Format, writer: W) -> io::Result<()> {
+    match format {
+        &Format::Csv => heatmap.write_csv(writer),
+        &Format::Json => heatmap.write_json(writer),
+        &Format::Svg => heatmap.write_svg(writer),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 || args.len() > 3 {
+        usage();
+    }
+    // checked before creating the output file, so a typo doesn't truncate it
+    let format = match parse_format(&args[1]) {
+        Some(format) => format,
+        None => usage(),
+    };
+    let heatmap = match Heatmap::load(&args[0]) {
+        Ok(heatmap) => heatmap,
+        Err(e) => {
+            eprintln!("can't load {}: {}", args[0], e);
+            process::exit(1);
+        }
+    };
+    let res = match args.get(2) {
+        Some(path) => File::create(path).and_then(|file| export(&heatmap, &format, file)),
+        None => {
+            let stdout = io::stdout();
+            let lock = stdout.lock();
+            export(&heatmap, &format, lock)
+        }
+    };
+    if let Err(e) = res {
+        eprintln!("can't export heatmap: {}", e);
+        process::exit(1);
+    }
+}
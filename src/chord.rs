@@ -0,0 +1,292 @@
+use std::process::{Child, Command};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use libusb;
+use libusb::Result as UsbResult;
+use uinput::event::{Keyboard as UinputKey};
+use g910::*;
+
+use backend::{self, KeyboardHandler, Leds};
+use error::Error;
+use geometry;
+use u_input::KeySink;
+
+/// Milliseconds the keys of a triggered chord are lit by default.
+const FLASH_DURATION: u64 = 300;
+/// Milliseconds between two checks whether a flash is over or a command has exited.
+const CHORD_INTERVAL: u64 = 50;
+
+/// What a chord does when triggered.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Runs the command with `sh -c` without waiting for it.
+    Command(String),
+    /// Taps the keys one after another on the sink set with `ChordHandler::sink`.
+    Keys(Vec<UinputKey>),
+    /// Switches to the profile with the given index through the handle set with
+    /// `ChordHandler::profiles`.
+    Profile(usize),
+}
+
+/// Triggers actions when combinations of keys are pressed, e.g. G1+G2 or
+/// LeftControl+LeftAlt+G5.
+///
+/// A chord triggers once all of its keys and no others are held down, in any order. It
+/// doesn't trigger again until one of its keys is pressed anew. Its keys are flashed to confirm
+/// the trigger and get their previous colors back afterwards.
+///
+/// The keys are still seen by all other handlers, e.g. a `UinputHandler` emits them as usual,
+/// so chords are best made of G-keys or keys without effect on their own.
+pub struct ChordHandler {
+    chords: Vec<(Vec<Key>, Action)>,
+    held: Vec<Key>,
+    triggered: bool,
+    sink: Option<Box<KeySink>>,
+    profiles: Option<Arc<Mutex<usize>>>,
+    flash_color: Color,
+    flash_duration: Duration,
+    /// The keys being flashed with their previous colors and when they are restored.
+    flashing: Option<(Vec<(Key, Color)>, Instant)>,
+    children: Vec<Child>,
+    on_error: Option<Box<FnMut(Error)>>,
+}
+
+impl ChordHandler {
+    pub fn new() -> ChordHandler {
+        ChordHandler {
+            chords: Vec::new(),
+            held: Vec::new(),
+            triggered: false,
+            sink: None,
+            profiles: None,
+            flash_color: Color::new(255, 255, 255),
+            flash_duration: Duration::from_millis(FLASH_DURATION),
+            flashing: None,
+            children: Vec::new(),
+            on_error: None,
+        }
+    }
+
+    /// Runs the action whenever the keys are held down together.
+    pub fn chord(mut self, keys: Vec<Key>, action: Action) -> ChordHandler {
+        self.chords.push((keys, action));
+        self
+    }
+
+    /// Sets the sink `Action::Keys` are emitted to, e.g. a device created with
+    /// `UinputBuilder::create_device`.
+    pub fn sink<S: KeySink + 'static>(mut self, sink: S) -> ChordHandler {
+        self.sink = Some(Box::new(sink));
+        self
+    }
+
+    /// Sets the handle `Action::Profile` switches, see `ProfileManager::profile_handle`.
+    pub fn profiles(mut self, handle: Arc<Mutex<usize>>) -> ChordHandler {
+        self.profiles = Some(handle);
+        self
+    }
+
+    /// Sets the color and duration the keys of a triggered chord are flashed with, defaults to
+    /// white for 300ms.
+    pub fn flash(mut self, color: Color, duration: Duration) -> ChordHandler {
+        self.flash_color = color;
+        self.flash_duration = duration;
+        self
+    }
+
+    /// Calls the function with errors of actions which don't stop the handler, e.g. a command
+    /// which can't be run or a chord emitting keys without a sink, and with the cause of failed
+    /// writes to the sink. They are ignored by default.
+    pub fn on_error<F: FnMut(Error) + 'static>(mut self, f: F) -> ChordHandler {
+        self.on_error = Some(Box::new(f));
+        self
+    }
+
+    fn report(&mut self, err: Error) {
+        if let Some(ref mut on_error) = self.on_error {
+            on_error(err);
+        }
+    }
+
+    /// Returns the chord made of exactly the held keys.
+    fn matching(&self) -> Option<usize> {
+        self.chords.iter().position(|&(ref keys, _)| {
+            keys.len() == self.held.len() && keys.iter().all(|k| self.held.contains(k))
+        })
+    }
+
+    fn run(&mut self, action: Action) -> UsbResult<()> {
+        match action {
+            Action::Command(cmd) => match Command::new("sh").arg("-c").arg(&cmd).spawn() {
+                Ok(child) => self.children.push(child),
+                Err(e) => self.report(e.into()),
+            },
+            Action::Keys(keys) => {
+                if let Err(e) = self.emit(&keys) {
+                    self.report(e);
+                    return Err(libusb::Error::Io);
+                }
+            },
+            Action::Profile(index) => match self.profiles {
+                Some(ref handle) => *handle.lock().unwrap() = index,
+                None => self.report(Error::Config("chord switches profiles, but no handle is set".to_string())),
+            },
+        }
+        Ok(())
+    }
+
+    /// Taps the keys on the sink, a missing sink is reported right away.
+    fn emit(&mut self, keys: &[UinputKey]) -> Result<(), Error> {
+        let sink = match self.sink {
+            Some(ref mut sink) => sink,
+            None => {
+                self.report(Error::Config("chord emits keys, but no sink is set".to_string()));
+                return Ok(());
+            },
+        };
+        for key in keys {
+            try!(sink.press(key));
+            try!(sink.synchronize());
+            try!(sink.release(key));
+            try!(sink.synchronize());
+        }
+        Ok(())
+    }
+
+    /// Restores the flashed keys, except for the ones another handler has drawn on since.
+    fn unflash(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        let keys = match self.flashing.take() {
+            Some((keys, _)) => keys,
+            None => return Ok(()),
+        };
+        let key_colors: Vec<_> = keys.into_iter()
+            .filter(|&(ref k, _)| keyboard.color(k) == Some(self.flash_color))
+            .map(|(k, previous)| KeyColor::new(k, previous))
+            .collect();
+        if key_colors.is_empty() {
+            return Ok(());
+        }
+        keyboard.set_key_colors(key_colors)
+    }
+}
+
+impl KeyboardHandler for ChordHandler {
+    #[allow(unused_variables)]
+    fn accept_key(&self, evt: &KeyEvent) -> bool {
+        true
+    }
+
+    fn handle_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        match evt {
+            &KeyEvent::KeyPressed(ref key) => {
+                if !self.held.contains(key) {
+                    self.held.push(key.clone());
+                }
+                self.triggered = false;
+            },
+            &KeyEvent::KeyReleased(ref key) => {
+                self.held.retain(|k| k != key);
+                return Ok(());
+            },
+        }
+        if self.triggered {
+            return Ok(());
+        }
+        let index = match self.matching() {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        self.triggered = true;
+        let (keys, action) = self.chords[index].clone();
+        try!(self.unflash(keyboard));
+        let lit: Vec<_> = keys.iter().filter(|k| geometry::is_lightable(k)).collect();
+        let previous = lit.iter()
+            .map(|&k| (k.clone(), keyboard.color(k).unwrap_or(Color::new(0, 0, 0))))
+            .collect();
+        if !lit.is_empty() {
+            try!(keyboard.set_key_colors(lit.iter()
+                .map(|&k| KeyColor::new(k.clone(), self.flash_color))
+                .collect()));
+        }
+        self.flashing = Some((previous, now + self.flash_duration));
+        self.run(action)
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(CHORD_INTERVAL))
+    }
+
+    fn handle_time(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        // reap exited commands
+        let mut i = 0;
+        while i < self.children.len() {
+            match self.children[i].try_wait() {
+                Ok(None) => i += 1,
+                Ok(Some(_)) | Err(_) => { self.children.swap_remove(i); },
+            }
+        }
+        if self.flashing.as_ref().map_or(false, |&(_, until)| until <= now) {
+            try!(self.unflash(keyboard));
+        }
+        Ok(())
+    }
+}
+
+impl From<ChordHandler> for Handler {
+    fn from(handler: ChordHandler) -> Handler {
+        backend::into_handler(handler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use g910::*;
+    use uinput::event::{Keyboard as UinputKey};
+    use uinput::event::keyboard::Key as UinputStandardKey;
+
+    use backend::Leds;
+    use mock::{Call, Harness, MockSink, SinkEvent};
+    use super::{Action, ChordHandler};
+
+    #[test]
+    fn emits_keys_and_restores_colors() {
+        let (g1, g2) = (Key::Gaming(GamingKey::G1), Key::Gaming(GamingKey::G2));
+        let tab = UinputKey::Key(UinputStandardKey::Tab);
+        let sink = MockSink::new();
+        let mut harness = Harness::new(ChordHandler::new()
+            .chord(vec![g1.clone(), g2.clone()], Action::Keys(vec![tab]))
+            .sink(sink.clone()));
+        harness.keyboard_mut().set_key_colors(vec![KeyColor::new(g1.clone(), Color::new(0, 0, 255))]).unwrap();
+        harness.press(g1.clone()).unwrap();
+        assert!(sink.events().is_empty());
+        harness.press(g2.clone()).unwrap();
+        assert_eq!(sink.events(), vec![
+            SinkEvent::Press(tab), SinkEvent::Synchronize, SinkEvent::Release(tab), SinkEvent::Synchronize,
+        ]);
+        assert_eq!(harness.keyboard().color(&g1), Some(Color::new(255, 255, 255)));
+        harness.advance(Duration::from_millis(300)).unwrap();
+        assert_eq!(harness.keyboard().color(&g1), Some(Color::new(0, 0, 255)));
+        assert_eq!(harness.keyboard().color(&g2), Some(Color::new(0, 0, 0)));
+    }
+
+    #[test]
+    fn flashes_only_lightable_keys_still_showing_the_flash() {
+        let (g1, g2, mute) = (Key::Gaming(GamingKey::G1), Key::Gaming(GamingKey::G2), Key::Media(MediaKey::Mute));
+        let mut harness = Harness::new(ChordHandler::new()
+            .chord(vec![g1.clone(), g2.clone(), mute.clone()], Action::Profile(1)));
+        harness.press(g1.clone()).unwrap();
+        harness.press(g2.clone()).unwrap();
+        harness.press(mute.clone()).unwrap();
+        assert_eq!(harness.keyboard().color(&g1), Some(Color::new(255, 255, 255)));
+        // e.g. a UinputHandler lighting a layer
+        harness.keyboard_mut().set_key_colors(vec![KeyColor::new(g2.clone(), Color::new(0, 255, 0))]).unwrap();
+        harness.advance(Duration::from_millis(300)).unwrap();
+        assert_eq!(harness.keyboard().color(&g1), Some(Color::new(0, 0, 0)));
+        assert_eq!(harness.keyboard().color(&g2), Some(Color::new(0, 255, 0)));
+        assert!(harness.keyboard().calls().iter().all(|call| match call {
+            &Call::SetKeyColors(ref colors) => colors.iter().all(|&(ref k, _)| *k != mute),
+            _ => true,
+        }));
+    }
+}
@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+use g910::{Color, Key, GamingKey, StandardKey};
+use toml::{self, Table, Value};
+use uinput::event::{Keyboard as UinputKey};
+use uinput::event::keyboard::Misc;
+
+use error::{Error, Result};
+use layout::Layout;
+
+/// Configuration of a `UinputHandler`, read from a TOML file like this:
+///
+/// ```toml
+/// # layout of the keyboard, overrides the one of the handler
+/// layout = "us"
+/// # time in ms after which a dual-role key counts as held
+/// tapping_term = 200
+///
+/// # emit another key instead of the pressed one
+/// [remap]
+/// CapsLock = "Esc"
+/// LeftAlt = "LeftWindows"
+/// LeftWindows = "LeftAlt"
+/// Menu = "RightControl"
+///
+/// # what the G- and M-keys emit, either a single key or a chord pressed in order
+/// [gaming]
+/// G1 = "Macro"
+/// G2 = ["LeftControl", "C"]
+/// G3 = []
+///
+/// # keys emitting one key when tapped and another one when held
+/// [dual]
+/// CapsLock = { tap = "Esc", hold = "LeftControl" }
+/// Space = { tap = "Space", layer = "nav" }
+///
+/// # alternate mappings active while holding the momentary key or toggled by the toggle key,
+/// # keys bound on active layers are lit in the layer's color
+/// [layers.nav]
+/// momentary = "RightAlt"
+/// toggle = "ScrollLock"
+/// color = "#00ff00"
+/// [layers.nav.keys]
+/// H = "Left"
+/// J = "Down"
+/// K = "Up"
+/// L = "Right"
+/// _1 = "F1"
+///
+/// # auto-repeat of held keys, with the delay in ms and the rate in repeats per second
+/// [repeat]
+/// delay = 600
+/// rate = 25
+/// [repeat.keys]
+/// Backspace = { delay = 200, rate = 40 }
+/// LeftShift = false
+/// ```
+///
+/// Keys are named by their legend on the configured layout (see `Layout::legend`) or by the
+/// name of the G910 key. The G- and M-keys can also emit `F13` to `F24` and `Macro`, which
+/// they do by default (see `default_gaming`).
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The layout of the keyboard, which also names the keys of the file.
+    pub layout: Option<Layout>,
+    /// Keys to emit instead of the pressed ones.
+    pub remap: HashMap<StandardKey, StandardKey>,
+    /// The keys emitted by G- and M-keys, in addition to the defaults.
+    pub gaming: HashMap<GamingKey, Vec<UinputKey>>,
+    /// The time after which a dual-role key counts as held.
+    pub tapping_term: Option<Duration>,
+    pub dual: HashMap<StandardKey, DualRole>,
+    pub layers: Vec<Layer>,
+    /// Keys activating a layer while held, by layer name.
+    pub momentary: HashMap<StandardKey, String>,
+    /// Keys turning a layer on and off, by layer name.
+    pub toggle: HashMap<StandardKey, String>,
+    /// The auto-repeat of all keys.
+    pub repeat: Option<Repeat>,
+    /// The auto-repeat of single keys, None disabling it.
+    pub key_repeat: HashMap<StandardKey, Option<Repeat>>,
+}
+
+/// How a held key is repeated, see `UinputHandler::repeat`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Repeat {
+    /// The time after which the first repeat is emitted.
+    pub delay: Duration,
+    /// The time between two repeats.
+    pub period: Duration,
+}
+
+impl Repeat {
+    /// Creates a repeat starting after `delay` with `rate` repeats per second.
+    pub fn new(delay: Duration, rate: u32) -> Repeat {
+        Repeat {
+            delay: delay,
+            period: Duration::new(0, 1_000_000_000 / rate.max(1)),
+        }
+    }
+}
+
+/// A key emitting one key when tapped and doing something else when held, see
+/// `UinputHandler::dual_role`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DualRole {
+    pub tap: UinputKey,
+    pub hold: Hold,
+}
+
+/// What a held dual-role key does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Hold {
+    Key(UinputKey),
+    /// Activates the layer with the given name while held.
+    Layer(String),
+}
+
+/// An alternate mapping of keys, see `UinputHandler::layer`.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub name: String,
+    /// The keys emitted instead of the bound keys, keys without binding are emitted as usual.
+    pub bindings: HashMap<StandardKey, Vec<UinputKey>>,
+    /// The color bound keys are lit in while the layer is active.
+    pub color: Color,
+}
+
+impl Layer {
+    /// Creates a layer without bindings, lit green.
+    pub fn new<S: Into<String>>(name: S) -> Layer {
+        Layer {
+            name: name.into(),
+            bindings: HashMap::new(),
+            color: Color::new(0, 255, 0),
+        }
+    }
+
+    /// Emits the given keys instead of `key` while the layer is active.
+    pub fn bind(mut self, key: StandardKey, emit: Vec<UinputKey>) -> Layer {
+        self.bindings.insert(key, emit);
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Layer {
+        self.color = color;
+        self
+    }
+}
+
+impl Config {
+    /// Creates an empty configuration.
+    pub fn new() -> Config {
+        Config {
+            layout: None,
+            remap: HashMap::new(),
+            gaming: HashMap::new(),
+            tapping_term: None,
+            dual: HashMap::new(),
+            layers: Vec::new(),
+            momentary: HashMap::new(),
+            toggle: HashMap::new(),
+            repeat: None,
+            key_repeat: HashMap::new(),
+        }
+    }
+
+    /// Reads the configuration from the given file, naming keys by the given layout unless the
+    /// file sets one.
+    pub fn load<P: AsRef<Path>>(path: P, layout: Layout) -> Result<Config> {
+        let mut s = String::new();
+        try!(File::open(path).and_then(|mut f| f.read_to_string(&mut s)));
+        Config::parse(&s, layout)
+    }
+
+    /// Parses the configuration from TOML.
+    pub fn parse(s: &str, layout: Layout) -> Result<Config> {
+        let mut parser = toml::Parser::new(s);
+        let table = match parser.parse() {
+            Some(table) => table,
+            None => {
+                let err = &parser.errors[0];
+                let (line, col) = parser.to_linecol(err.lo);
+                return Err(Error::Config(format!("{}:{}: {}", line + 1, col + 1, err.desc)));
+            }
+        };
+
+        let mut config = Config::new();
+        if let Some(name) = table.get("layout") {
+            let name = try!(string(name, "layout"));
+            config.layout = Some(try!(Layout::from_name(name)
+                .ok_or_else(|| Error::Config(format!("unknown layout {:?}", name)))));
+        }
+        let layout = config.layout.unwrap_or(layout);
+
+        if let Some(remap) = try!(section(&table, "remap")) {
+            for (from, to) in remap {
+                let from = try!(standard_key(layout, from));
+                let to = try!(standard_key(layout, try!(string(to, "remap target"))));
+                config.remap.insert(from, to);
+            }
+        }
+        if let Some(gaming) = try!(section(&table, "gaming")) {
+            for (key, emit) in gaming {
+                let key = try!(gaming_key(key));
+                config.gaming.insert(key, try!(chord(layout, emit, "gaming target")));
+            }
+        }
+        if let Some(term) = table.get("tapping_term") {
+            config.tapping_term = Some(Duration::from_millis(try!(positive(term, "tapping_term"))));
+        }
+        if let Some(dual) = try!(section(&table, "dual")) {
+            for (key, roles) in dual {
+                let key = try!(standard_key(layout, key));
+                let roles = try!(roles.as_table()
+                    .ok_or_else(|| Error::Config(format!("dual role of {:?} must be a table", key))));
+                let tap = match roles.get("tap") {
+                    Some(value) => try!(string(value, "tap").and_then(|name| uinput_key(layout, name))),
+                    None => return Err(Error::Config(format!("dual role of {:?} is missing tap", key))),
+                };
+                let hold = match (roles.get("hold"), roles.get("layer")) {
+                    (Some(value), None) => Hold::Key(try!(string(value, "hold")
+                        .and_then(|name| uinput_key(layout, name)))),
+                    (None, Some(value)) => Hold::Layer(try!(string(value, "layer")).to_string()),
+                    _ => return Err(Error::Config(format!("dual role of {:?} needs either hold or layer", key))),
+                };
+                config.dual.insert(key, DualRole {
+                    tap: tap,
+                    hold: hold,
+                });
+            }
+        }
+        if let Some(layers) = try!(section(&table, "layers")) {
+            for (name, table) in layers {
+                let table = try!(table.as_table()
+                    .ok_or_else(|| Error::Config(format!("layer {} must be a table", name))));
+                let mut layer = Layer::new(name.clone());
+                if let Some(value) = table.get("color") {
+                    layer.color = try!(color(try!(string(value, "color"))));
+                }
+                if let Some(value) = table.get("momentary") {
+                    let key = try!(standard_key(layout, try!(string(value, "momentary"))));
+                    config.momentary.insert(key, name.clone());
+                }
+                if let Some(value) = table.get("toggle") {
+                    let key = try!(standard_key(layout, try!(string(value, "toggle"))));
+                    config.toggle.insert(key, name.clone());
+                }
+                if let Some(keys) = try!(section(table, "keys")) {
+                    for (key, emit) in keys {
+                        let key = try!(standard_key(layout, key));
+                        layer.bindings.insert(key, try!(chord(layout, emit, "layer binding")));
+                    }
+                }
+                config.layers.push(layer);
+            }
+        }
+        for (key, role) in &config.dual {
+            if let Hold::Layer(ref name) = role.hold {
+                if !config.layers.iter().any(|l| l.name == *name) {
+                    return Err(Error::Config(format!("dual role of {:?} holds unknown layer {:?}", key, name)));
+                }
+            }
+        }
+        if let Some(repeat) = try!(section(&table, "repeat")) {
+            if repeat.contains_key("delay") || repeat.contains_key("rate") {
+                config.repeat = Some(try!(repeat_of(repeat, "repeat")));
+            }
+            if let Some(keys) = try!(section(repeat, "keys")) {
+                for (key, value) in keys {
+                    let key = try!(standard_key(layout, key));
+                    let repeat = match value {
+                        &Value::Boolean(false) => None,
+                        &Value::Table(ref table) => Some(try!(repeat_of(table, "key repeat"))),
+                        _ => return Err(Error::Config(format!("repeat of {:?} must be a table or false", key))),
+                    };
+                    config.key_repeat.insert(key, repeat);
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Returns the keys emitted by the G- and M-keys if not configured otherwise: `F13` to `F21`
+/// for G1 to G9, `F22` to `F24` for M1 to M3 and `Macro` for MR.
+pub fn default_gaming() -> HashMap<GamingKey, Vec<UinputKey>> {
+    use g910::GamingKey::*;
+    let keys = [
+        (G1, Misc::F13), (G2, Misc::F14), (G3, Misc::F15), (G4, Misc::F16), (G5, Misc::F17),
+        (G6, Misc::F18), (G7, Misc::F19), (G8, Misc::F20), (G9, Misc::F21),
+        (M1, Misc::F22), (M2, Misc::F23), (M3, Misc::F24), (MR, Misc::Macro),
+    ];
+    keys.iter().map(|&(g, m)| (g, vec![UinputKey::Misc(m)])).collect()
+}
+
+fn section<'a>(table: &'a Table, name: &str) -> Result<Option<&'a Table>> {
+    match table.get(name) {
+        Some(value) => value.as_table()
+            .map(Some)
+            .ok_or_else(|| Error::Config(format!("{} must be a table", name))),
+        None => Ok(None),
+    }
+}
+
+fn string<'a>(value: &'a Value, what: &str) -> Result<&'a str> {
+    value.as_str().ok_or_else(|| Error::Config(format!("{} must be a string", what)))
+}
+
+fn positive(value: &Value, what: &str) -> Result<u64> {
+    value.as_integer()
+        .and_then(|i| if i > 0 { Some(i as u64) } else { None })
+        .ok_or_else(|| Error::Config(format!("{} must be a positive integer", what)))
+}
+
+/// Parses a table with the delay in ms and the rate in repeats per second.
+fn repeat_of(table: &Table, what: &str) -> Result<Repeat> {
+    match (table.get("delay"), table.get("rate")) {
+        (Some(delay), Some(rate)) => Ok(Repeat::new(
+            Duration::from_millis(try!(positive(delay, "repeat delay"))),
+            try!(positive(rate, "repeat rate")) as u32)),
+        _ => Err(Error::Config(format!("{} needs both delay and rate", what))),
+    }
+}
+
+/// Parses a single key or an array of keys.
+fn chord(layout: Layout, value: &Value, what: &str) -> Result<Vec<UinputKey>> {
+    match value {
+        &Value::Array(ref keys) => {
+            let mut chord = Vec::new();
+            for key in keys {
+                chord.push(try!(uinput_key(layout, try!(string(key, what)))));
+            }
+            Ok(chord)
+        },
+        key => Ok(vec![try!(uinput_key(layout, try!(string(key, what))))]),
+    }
+}
+
+/// Parses a `#rrggbb` color.
+fn color(s: &str) -> Result<Color> {
+    let err = || Error::Config(format!("color {:?} isn't of the form #rrggbb", s));
+    if s.len() != 7 || !s.starts_with('#') {
+        return Err(err());
+    }
+    let component = |i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| err());
+    Ok(Color::new(try!(component(1)), try!(component(3)), try!(component(5))))
+}
+
+fn gaming_key(name: &str) -> Result<GamingKey> {
+    Key::values().into_iter()
+        .filter_map(|key| match key {
+            Key::Gaming(g) if g != GamingKey::None => Some(g),
+            _ => None,
+        })
+        .find(|g| format!("{:?}", g) == name)
+        .ok_or_else(|| Error::Config(format!("unknown gaming key {:?}", name)))
+}
+
+fn uinput_key(layout: Layout, name: &str) -> Result<UinputKey> {
+    let misc = match name {
+        "F13" => Some(Misc::F13), "F14" => Some(Misc::F14), "F15" => Some(Misc::F15),
+        "F16" => Some(Misc::F16), "F17" => Some(Misc::F17), "F18" => Some(Misc::F18),
+        "F19" => Some(Misc::F19), "F20" => Some(Misc::F20), "F21" => Some(Misc::F21),
+        "F22" => Some(Misc::F22), "F23" => Some(Misc::F23), "F24" => Some(Misc::F24),
+        "Macro" => Some(Misc::Macro),
+        _ => None,
+    };
+    if let Some(misc) = misc {
+        return Ok(UinputKey::Misc(misc));
+    }
+    let key = try!(standard_key(layout, name));
+    layout.to_uinput_key(key)
+        .ok_or_else(|| Error::Config(format!("key {:?} can't be emitted on this layout", name)))
+}
+
+fn standard_key(layout: Layout, name: &str) -> Result<StandardKey> {
+    layout.key_for_legend(name).ok_or_else(|| Error::Config(format!("unknown key {:?}", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use g910::StandardKey;
+
+    use layout::Layout;
+    use super::{Config, Hold};
+
+    /// Returns the TOML example of the documentation of `Config`.
+    fn doc_example() -> String {
+        include_str!("config.rs").lines()
+            .map(|line| line.trim())
+            .skip_while(|line| *line != "/// ```toml")
+            .skip(1)
+            .take_while(|line| *line != "/// ```")
+            .map(|line| format!("{}\n", line[3..].trim()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_doc_example() {
+        let example = doc_example();
+        assert!(example.contains("[dual]"));
+        let config = Config::parse(&example, Layout::DeQwertz).unwrap();
+        assert_eq!(config.layout, Some(Layout::UsAnsi));
+        assert_eq!(config.tapping_term, Some(Duration::from_millis(200)));
+        assert_eq!(config.dual[&StandardKey::Space].hold, Hold::Layer("nav".to_string()));
+        assert_eq!(config.layers.len(), 1);
+        assert_eq!(config.momentary[&StandardKey::RightAlt], "nav");
+        assert_eq!(config.key_repeat[&StandardKey::LeftShift], None);
+    }
+
+    #[test]
+    fn rejects_unknown_layer() {
+        let config = "[dual]\nSpace = { tap = \"Space\", layer = \"nav\" }\n";
+        assert!(Config::parse(config, Layout::UsAnsi).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::result::Result as StdResult;
+use uinput;
+
+pub type Result<T> = StdResult<T, Error>;
+
+/// Errors which can occur while setting up a handler.
+#[derive(Debug)]
+pub enum Error {
+    /// The virtual device couldn't be created, e.g. due to missing permissions.
+    Device(io::Error),
+    /// Writing to a `uinput::Device` used as sink failed.
+    Uinput(uinput::Error),
+    Io(io::Error),
+    /// A configuration file is invalid.
+    Config(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Error::Device(ref e) => write!(f, "can't create the virtual device: {}", e),
+            &Error::Uinput(ref e) => write!(f, "uinput error: {}", e),
+            &Error::Io(ref e) => write!(f, "io error: {}", e),
+            &Error::Config(ref msg) => write!(f, "config error: {}", msg),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match self {
+            &Error::Device(ref e) => e.description(),
+            &Error::Uinput(ref e) => e.description(),
+            &Error::Io(ref e) => e.description(),
+            &Error::Config(ref msg) => msg,
+        }
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match self {
+            &Error::Device(ref e) => Some(e),
+            &Error::Uinput(ref e) => Some(e),
+            &Error::Io(ref e) => Some(e),
+            &Error::Config(_) => None,
+        }
+    }
+}
+
+impl From<uinput::Error> for Error {
+    fn from(err: uinput::Error) -> Error {
+        Error::Uinput(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
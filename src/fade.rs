@@ -0,0 +1,192 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use libusb::Result as UsbResult;
+use g910::*;
+
+use backend::{self, secs, KeyboardHandler, Leds};
+use gradient::{Gradient, Interpolation};
+
+/// Milliseconds between two frames while keys are fading.
+const FRAME_INTERVAL: u64 = 30;
+
+/// Lights pressed keys and fades each of them back to the base color after its release.
+///
+/// Only keys whose color changed since the last frame are sent, all in one `set_key_colors`.
+pub struct FadeHandler {
+    base: Color,
+    color: Color,
+    /// Blends from the pressed color at 0 to the base color at 1.
+    gradient: Gradient,
+    duration: Duration,
+    held: HashSet<Key>,
+    /// The release of every fading key.
+    fading: HashMap<Key, Instant>,
+    /// The color of every key not showing the base color.
+    shown: HashMap<Key, Color>,
+}
+
+impl FadeHandler {
+    /// Creates a handler lighting keys white and fading them to black within a second.
+    pub fn new() -> FadeHandler {
+        let base = Color::new(0, 0, 0);
+        let color = Color::new(255, 255, 255);
+        FadeHandler {
+            base: base,
+            color: color,
+            gradient: Gradient::evenly(&[color, base], Interpolation::Rgb),
+            duration: Duration::from_secs(1),
+            held: HashSet::new(),
+            fading: HashMap::new(),
+            shown: HashMap::new(),
+        }
+    }
+
+    /// Sets the color of pressed keys.
+    pub fn color(mut self, color: Color) -> FadeHandler {
+        self.color = color;
+        self.gradient = Gradient::evenly(&[self.color, self.base], Interpolation::Rgb);
+        self
+    }
+
+    /// Sets the color of keys at rest.
+    pub fn base(mut self, base: Color) -> FadeHandler {
+        self.base = base;
+        self.gradient = Gradient::evenly(&[self.color, self.base], Interpolation::Rgb);
+        self
+    }
+
+    /// Fades through the given gradient instead, from the pressed color at 0 to the base color
+    /// at 1, e.g. to fade through the hues of `Gradient::heatmap`.
+    pub fn gradient(mut self, gradient: Gradient) -> FadeHandler {
+        self.color = gradient.color_at(0.0);
+        self.base = gradient.color_at(1.0);
+        self.gradient = gradient;
+        self
+    }
+
+    /// Sets how long a released key takes to fade back to the base color.
+    pub fn duration(mut self, duration: Duration) -> FadeHandler {
+        self.duration = duration;
+        self
+    }
+
+    fn draw(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        let duration = self.duration;
+        let mut key_colors = Vec::new();
+        let mut done = Vec::new();
+        for (key, &released) in &self.fading {
+            let t = secs(now.duration_since(released)) / secs(duration);
+            let color = if t >= 1.0 {
+                done.push(key.clone());
+                self.base
+            } else {
+                self.gradient.color_at(t)
+            };
+            if self.shown.get(key) != Some(&color) {
+                key_colors.push(KeyColor::new(key.clone(), color));
+            }
+        }
+        for key in done {
+            self.fading.remove(&key);
+        }
+        for kc in &key_colors {
+            if kc.color == self.base {
+                self.shown.remove(&kc.key);
+            } else {
+                self.shown.insert(kc.key.clone(), kc.color);
+            }
+        }
+        if key_colors.is_empty() {
+            return Ok(());
+        }
+        keyboard.set_key_colors(key_colors)
+    }
+}
+
+impl KeyboardHandler for FadeHandler {
+    fn init(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        self.shown.clear();
+        keyboard.set_all_colors(self.base)
+    }
+
+    fn redraw(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        try!(keyboard.set_all_colors(self.base));
+        if self.shown.is_empty() {
+            return Ok(());
+        }
+        keyboard.set_key_colors(self.shown.iter()
+            .map(|(key, &color)| KeyColor::new(key.clone(), color))
+            .collect())
+    }
+
+    fn accept_key(&self, evt: &KeyEvent) -> bool {
+        let key = match evt {
+            &KeyEvent::KeyPressed(ref k) => k,
+            &KeyEvent::KeyReleased(ref k) => k,
+        };
+        match key {
+            // media keys can't be lit
+            &Key::Media(_) => false,
+            _ => true,
+        }
+    }
+
+    fn handle_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        match evt {
+            &KeyEvent::KeyPressed(ref key) => {
+                self.held.insert(key.clone());
+                self.fading.remove(key);
+                if self.shown.get(key) == Some(&self.color) {
+                    return Ok(());
+                }
+                self.shown.insert(key.clone(), self.color);
+                keyboard.set_key_colors(vec![KeyColor::new(key.clone(), self.color)])
+            },
+            &KeyEvent::KeyReleased(ref key) => {
+                if self.held.remove(key) {
+                    self.fading.insert(key.clone(), now);
+                }
+                Ok(())
+            },
+        }
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(FRAME_INTERVAL))
+    }
+
+    fn handle_time(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        self.draw(now, keyboard)
+    }
+}
+
+impl From<FadeHandler> for Handler {
+    fn from(handler: FadeHandler) -> Handler {
+        backend::into_handler(handler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use g910::*;
+
+    use mock::Harness;
+    use super::FadeHandler;
+
+    #[test]
+    fn fades_after_release() {
+        let mut harness = Harness::new(FadeHandler::new());
+        let a = Key::Standard(StandardKey::A);
+        harness.init().unwrap();
+        harness.press(a.clone()).unwrap();
+        harness.advance(Duration::from_secs(2)).unwrap();
+        assert_eq!(harness.keyboard().color(&a), Some(Color::new(255, 255, 255)));
+        harness.release(a.clone()).unwrap();
+        harness.advance(Duration::from_millis(510)).unwrap();
+        let half = harness.keyboard().color(&a).unwrap();
+        assert!(half.red > 0 && half.red < 255);
+        harness.advance(Duration::from_millis(510)).unwrap();
+        assert_eq!(harness.keyboard().color(&a), Some(Color::new(0, 0, 0)));
+    }
+}
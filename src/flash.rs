@@ -1,41 +1,208 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
 use libusb::Result as UsbResult;
 use g910::*;
 
-pub struct FlashHandler;
+use backend::{self, KeyboardHandler, Leds};
+
+/// Lights the board in the pressed color while any key is held, in the idle color otherwise.
+pub struct FlashHandler {
+    idle: Color,
+    pressed: Color,
+    per_key: bool,
+    held: HashSet<Key>,
+    timeout: Option<Duration>,
+    last_event: Option<Instant>,
+}
 
 impl FlashHandler {
+    /// Creates a handler lighting the board blue, and red while keys are held.
     pub fn new() -> FlashHandler {
-        FlashHandler { }
+        FlashHandler {
+            idle: Color::new(0, 0, 255),
+            pressed: Color::new(255, 0, 0),
+            per_key: false,
+            held: HashSet::new(),
+            timeout: None,
+            last_event: None,
+        }
+    }
+
+    pub fn idle(mut self, color: Color) -> FlashHandler {
+        self.idle = color;
+        self
+    }
+
+    pub fn pressed(mut self, color: Color) -> FlashHandler {
+        self.pressed = color;
+        self
+    }
+
+    /// Lights only the held keys instead of the whole board.
+    pub fn per_key(mut self, per_key: bool) -> FlashHandler {
+        self.per_key = per_key;
+        self
+    }
+
+    /// Sets the time without any key event after which all keys are assumed to be released,
+    /// in case a release got lost. Off by default, as keys like modifiers may really be held
+    /// that long.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> FlashHandler {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the number of keys currently held.
+    pub fn held(&self) -> usize {
+        self.held.len()
+    }
+
+    fn draw(&self, keyboard: &mut Leds) -> UsbResult<()> {
+        if !self.per_key {
+            return keyboard.set_all_colors(if self.held.is_empty() { self.idle } else { self.pressed });
+        }
+        try!(keyboard.set_all_colors(self.idle));
+        if self.held.is_empty() {
+            return Ok(());
+        }
+        keyboard.set_key_colors(self.held.iter()
+            .map(|key| KeyColor::new(key.clone(), self.pressed))
+            .collect())
+    }
+}
+
+impl KeyboardHandler for FlashHandler {
+    fn init(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        self.draw(keyboard)
     }
 
-    fn init(&mut self, keyboard: &mut Keyboard) -> UsbResult<()> {
-        keyboard.set_all_colors(Color::new(0, 0, 255))
+    fn redraw(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        self.draw(keyboard)
     }
 
-    #[allow(unused_variables)]
     fn accept_key(&self, evt: &KeyEvent) -> bool {
-        true
+        let key = match evt {
+            &KeyEvent::KeyPressed(ref k) => k,
+            &KeyEvent::KeyReleased(ref k) => k,
+        };
+        match key {
+            // media keys can't be lit
+            &Key::Media(_) => false,
+            _ => true,
+        }
     }
 
-    fn handle_key(&mut self, evt: &KeyEvent, keyboard: &mut Keyboard) -> UsbResult<()> {
+    fn handle_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        self.last_event = Some(now);
         match evt {
-            &KeyEvent::KeyPressed(_) => {
-                keyboard.set_all_colors(Color::new(255, 0, 0))
+            &KeyEvent::KeyPressed(ref key) => {
+                let was_idle = self.held.is_empty();
+                if !self.held.insert(key.clone()) {
+                    return Ok(());
+                }
+                if self.per_key {
+                    keyboard.set_key_colors(vec![KeyColor::new(key.clone(), self.pressed)])
+                } else if was_idle {
+                    keyboard.set_all_colors(self.pressed)
+                } else {
+                    Ok(())
+                }
             },
-            &KeyEvent::KeyReleased(_) => {
-                keyboard.set_all_colors(Color::new(0, 0, 255))
+            &KeyEvent::KeyReleased(ref key) => {
+                if !self.held.remove(key) {
+                    return Ok(());
+                }
+                if self.per_key {
+                    keyboard.set_key_colors(vec![KeyColor::new(key.clone(), self.idle)])
+                } else if self.held.is_empty() {
+                    keyboard.set_all_colors(self.idle)
+                } else {
+                    Ok(())
+                }
             },
         }
     }
+
+    fn interval(&self) -> Option<Duration> {
+        self.timeout.map(|timeout| timeout / 10)
+    }
+
+    fn handle_time(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        let timed_out = match (self.timeout, self.last_event) {
+            (Some(timeout), Some(last)) => now.duration_since(last) >= timeout,
+            _ => false,
+        };
+        if !timed_out || self.held.is_empty() {
+            return Ok(());
+        }
+        self.held.clear();
+        self.draw(keyboard)
+    }
 }
 
 impl From<FlashHandler> for Handler {
     fn from(handler: FlashHandler) -> Handler {
-        HandlerBuilder::new(handler)
-            .init_fn(|handler, keyboard| handler.init(keyboard))
-            .accept_key_fn(|handler, evt| handler.accept_key(evt))
-            .handle_key_fn(|handler, evt, keyboard| handler.handle_key(evt, keyboard))
-            .build()
+        backend::into_handler(handler)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use g910::*;
+
+    use mock::Harness;
+    use super::FlashHandler;
+
+    #[test]
+    fn flashes_while_pressed() {
+        let mut harness = Harness::new(FlashHandler::new());
+        let a = Key::Standard(StandardKey::A);
+        harness.init().unwrap();
+        assert_eq!(harness.keyboard().color(&a), Some(Color::new(0, 0, 255)));
+        harness.press(a.clone()).unwrap();
+        assert!(harness.keyboard().frame().values().all(|&c| c == Color::new(255, 0, 0)));
+        harness.release(a.clone()).unwrap();
+        assert!(harness.keyboard().frame().values().all(|&c| c == Color::new(0, 0, 255)));
+    }
+
+    #[test]
+    fn stays_pressed_while_keys_are_held() {
+        let mut harness = Harness::new(FlashHandler::new());
+        let (a, b) = (Key::Standard(StandardKey::A), Key::Standard(StandardKey::B));
+        harness.init().unwrap();
+        harness.press(a.clone()).unwrap();
+        harness.press(b.clone()).unwrap();
+        harness.release(a.clone()).unwrap();
+        assert_eq!(harness.keyboard().color(&a), Some(Color::new(255, 0, 0)));
+        // keys are held as long as it takes without a timeout
+        harness.advance(Duration::from_secs(60)).unwrap();
+        assert_eq!(harness.handler().held(), 1);
+        harness.release(b).unwrap();
+        assert_eq!(harness.keyboard().color(&a), Some(Color::new(0, 0, 255)));
+    }
+
+    #[test]
+    fn times_out_lost_releases() {
+        let mut harness = Harness::new(FlashHandler::new().per_key(true)
+            .timeout(Some(Duration::from_secs(10))));
+        let a = Key::Standard(StandardKey::A);
+        harness.init().unwrap();
+        harness.press(a.clone()).unwrap();
+        assert_eq!(harness.keyboard().color(&a), Some(Color::new(255, 0, 0)));
+        harness.advance(Duration::from_secs(9)).unwrap();
+        assert_eq!(harness.handler().held(), 1);
+        harness.advance(Duration::from_secs(1)).unwrap();
+        assert_eq!(harness.handler().held(), 0);
+        assert_eq!(harness.keyboard().color(&a), Some(Color::new(0, 0, 255)));
+    }
+
+    #[test]
+    fn ignores_media_keys() {
+        let mut harness = Harness::new(FlashHandler::new().per_key(true));
+        harness.init().unwrap();
+        harness.press(Key::Media(MediaKey::Mute)).unwrap();
+        assert_eq!(harness.handler().held(), 0);
+        assert!(harness.keyboard().frame().values().all(|&c| c == Color::new(0, 0, 255)));
+    }
+}
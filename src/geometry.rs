@@ -0,0 +1,363 @@
+//! Physical positions of the keys of the G910.
+//!
+//! Positions and sizes are in millimetres, measured from the top left corner of the
+//! top left key, with y growing downwards:
+//!
+//! ```ignore
+//! let geometry = Geometry::new(Variant::Iso);
+//! let (x, y) = geometry.center(&Key::Standard(StandardKey::F)).unwrap();
+//! let below = geometry.nearest(x, y + 19.05, |_| true);
+//! ```
+
+use std::cmp::Ordering;
+use g910::*;
+use g910::StandardKey as K;
+
+/// Distance between two neighbouring keys in millimetres.
+pub const KEY_PITCH: f64 = 19.05;
+
+/// Offset of Esc from the top left corner in key units, making room for the G-keys on the left
+/// and the M-, G- and media keys on top.
+const STANDARD_OFFSET: (f64, f64) = (1.5, 1.25);
+
+/// Position of the standard keys on the ISO G910 in key units relative to Esc:
+/// (key, x, y, width, height)
+const ISO: &'static [(StandardKey, f64, f64, f64, f64)] = &[
+    (K::Esc, 0.0, 0.0, 1.0, 1.0),
+    (K::F1, 2.0, 0.0, 1.0, 1.0), (K::F2, 3.0, 0.0, 1.0, 1.0), (K::F3, 4.0, 0.0, 1.0, 1.0), (K::F4, 5.0, 0.0, 1.0, 1.0),
+    (K::F5, 6.5, 0.0, 1.0, 1.0), (K::F6, 7.5, 0.0, 1.0, 1.0), (K::F7, 8.5, 0.0, 1.0, 1.0), (K::F8, 9.5, 0.0, 1.0, 1.0),
+    (K::F9, 11.0, 0.0, 1.0, 1.0), (K::F10, 12.0, 0.0, 1.0, 1.0), (K::F11, 13.0, 0.0, 1.0, 1.0), (K::F12, 14.0, 0.0, 1.0, 1.0),
+    (K::Print, 15.25, 0.0, 1.0, 1.0), (K::ScrollLock, 16.25, 0.0, 1.0, 1.0), (K::Pause, 17.25, 0.0, 1.0, 1.0),
+
+    (K::Circumflex, 0.0, 1.5, 1.0, 1.0), (K::_1, 1.0, 1.5, 1.0, 1.0), (K::_2, 2.0, 1.5, 1.0, 1.0), (K::_3, 3.0, 1.5, 1.0, 1.0),
+    (K::_4, 4.0, 1.5, 1.0, 1.0), (K::_5, 5.0, 1.5, 1.0, 1.0), (K::_6, 6.0, 1.5, 1.0, 1.0), (K::_7, 7.0, 1.5, 1.0, 1.0),
+    (K::_8, 8.0, 1.5, 1.0, 1.0), (K::_9, 9.0, 1.5, 1.0, 1.0), (K::_0, 10.0, 1.5, 1.0, 1.0), (K::Sz, 11.0, 1.5, 1.0, 1.0),
+    (K::Tick, 12.0, 1.5, 1.0, 1.0), (K::Backspace, 13.0, 1.5, 2.0, 1.0),
+    (K::Insert, 15.25, 1.5, 1.0, 1.0), (K::Home, 16.25, 1.5, 1.0, 1.0), (K::PageUp, 17.25, 1.5, 1.0, 1.0),
+    (K::NumLock, 18.5, 1.5, 1.0, 1.0), (K::NumSlash, 19.5, 1.5, 1.0, 1.0), (K::NumStar, 20.5, 1.5, 1.0, 1.0),
+    (K::NumMinus, 21.5, 1.5, 1.0, 1.0),
+
+    (K::Tab, 0.0, 2.5, 1.5, 1.0), (K::Q, 1.5, 2.5, 1.0, 1.0), (K::W, 2.5, 2.5, 1.0, 1.0), (K::E, 3.5, 2.5, 1.0, 1.0),
+    (K::R, 4.5, 2.5, 1.0, 1.0), (K::T, 5.5, 2.5, 1.0, 1.0), (K::Z, 6.5, 2.5, 1.0, 1.0), (K::U, 7.5, 2.5, 1.0, 1.0),
+    (K::I, 8.5, 2.5, 1.0, 1.0), (K::O, 9.5, 2.5, 1.0, 1.0), (K::P, 10.5, 2.5, 1.0, 1.0), (K::Uuml, 11.5, 2.5, 1.0, 1.0),
+    (K::Plus, 12.5, 2.5, 1.0, 1.0), (K::Return, 13.75, 2.5, 1.25, 2.0),
+    (K::Delete, 15.25, 2.5, 1.0, 1.0), (K::End, 16.25, 2.5, 1.0, 1.0), (K::PageDown, 17.25, 2.5, 1.0, 1.0),
+    (K::Num7, 18.5, 2.5, 1.0, 1.0), (K::Num8, 19.5, 2.5, 1.0, 1.0), (K::Num9, 20.5, 2.5, 1.0, 1.0),
+    (K::NumPlus, 21.5, 2.5, 1.0, 2.0),
+
+    (K::CapsLock, 0.0, 3.5, 1.75, 1.0), (K::A, 1.75, 3.5, 1.0, 1.0), (K::S, 2.75, 3.5, 1.0, 1.0), (K::D, 3.75, 3.5, 1.0, 1.0),
+    (K::F, 4.75, 3.5, 1.0, 1.0), (K::G, 5.75, 3.5, 1.0, 1.0), (K::H, 6.75, 3.5, 1.0, 1.0), (K::J, 7.75, 3.5, 1.0, 1.0),
+    (K::K, 8.75, 3.5, 1.0, 1.0), (K::L, 9.75, 3.5, 1.0, 1.0), (K::Ouml, 10.75, 3.5, 1.0, 1.0), (K::Auml, 11.75, 3.5, 1.0, 1.0),
+    (K::Sharp, 12.75, 3.5, 1.0, 1.0),
+    (K::Num4, 18.5, 3.5, 1.0, 1.0), (K::Num5, 19.5, 3.5, 1.0, 1.0), (K::Num6, 20.5, 3.5, 1.0, 1.0),
+
+    (K::LeftShift, 0.0, 4.5, 1.25, 1.0), (K::SmallerThan, 1.25, 4.5, 1.0, 1.0), (K::Y, 2.25, 4.5, 1.0, 1.0),
+    (K::X, 3.25, 4.5, 1.0, 1.0), (K::C, 4.25, 4.5, 1.0, 1.0), (K::V, 5.25, 4.5, 1.0, 1.0), (K::B, 6.25, 4.5, 1.0, 1.0),
+    (K::N, 7.25, 4.5, 1.0, 1.0), (K::M, 8.25, 4.5, 1.0, 1.0), (K::Comma, 9.25, 4.5, 1.0, 1.0), (K::Dot, 10.25, 4.5, 1.0, 1.0),
+    (K::Minus, 11.25, 4.5, 1.0, 1.0), (K::RightShift, 12.25, 4.5, 2.75, 1.0),
+    (K::Up, 16.25, 4.5, 1.0, 1.0),
+    (K::Num1, 18.5, 4.5, 1.0, 1.0), (K::Num2, 19.5, 4.5, 1.0, 1.0), (K::Num3, 20.5, 4.5, 1.0, 1.0),
+    (K::NumReturn, 21.5, 4.5, 1.0, 2.0),
+
+    (K::LeftControl, 0.0, 5.5, 1.25, 1.0), (K::LeftWindows, 1.25, 5.5, 1.25, 1.0), (K::LeftAlt, 2.5, 5.5, 1.25, 1.0),
+    (K::Space, 3.75, 5.5, 6.25, 1.0), (K::RightAlt, 10.0, 5.5, 1.25, 1.0), (K::RightWindows, 11.25, 5.5, 1.25, 1.0),
+    (K::Menu, 12.5, 5.5, 1.25, 1.0), (K::RightControl, 13.75, 5.5, 1.25, 1.0),
+    (K::Left, 15.25, 5.5, 1.0, 1.0), (K::Down, 16.25, 5.5, 1.0, 1.0), (K::Right, 17.25, 5.5, 1.0, 1.0),
+    (K::Num0, 18.5, 5.5, 2.0, 1.0), (K::NumComma, 20.5, 5.5, 1.0, 1.0),
+];
+
+/// Keys of the ANSI G910 differing from the ISO one, which additionally lacks Sharp and
+/// SmallerThan.
+const ANSI: &'static [(StandardKey, f64, f64, f64, f64)] = &[
+    (K::Pipe, 13.5, 2.5, 1.5, 1.0), (K::Return, 12.75, 3.5, 2.25, 1.0), (K::LeftShift, 0.0, 4.5, 2.25, 1.0),
+];
+
+/// Position of the other keys in key units relative to the top left corner:
+/// (key, x, y, width, height)
+const OTHER: &'static [(Key, f64, f64, f64, f64)] = &[
+    (Key::Logo(LogoKey::G), 0.0, 0.0, 1.0, 1.0),
+    (Key::Gaming(GamingKey::M1), 1.5, 0.0, 1.0, 1.0), (Key::Gaming(GamingKey::M2), 2.5, 0.0, 1.0, 1.0),
+    (Key::Gaming(GamingKey::M3), 3.5, 0.0, 1.0, 1.0), (Key::Gaming(GamingKey::MR), 4.5, 0.0, 1.0, 1.0),
+    (Key::Gaming(GamingKey::G6), 6.0, 0.0, 1.0, 1.0), (Key::Gaming(GamingKey::G7), 7.0, 0.0, 1.0, 1.0),
+    (Key::Gaming(GamingKey::G8), 8.0, 0.0, 1.0, 1.0), (Key::Gaming(GamingKey::G9), 9.0, 0.0, 1.0, 1.0),
+    (Key::Logo(LogoKey::G910), 11.0, 0.0, 2.0, 1.0),
+    (Key::Media(MediaKey::Mute), 16.75, 0.0, 1.0, 1.0),
+    (Key::Media(MediaKey::VolumeDown), 17.75, 0.0, 1.0, 1.0),
+    (Key::Media(MediaKey::VolumeUp), 18.75, 0.0, 1.0, 1.0),
+    (Key::Media(MediaKey::Stop), 20.0, 0.0, 1.0, 1.0), (Key::Media(MediaKey::Backward), 21.0, 0.0, 1.0, 1.0),
+    (Key::Media(MediaKey::PlayPause), 22.0, 0.0, 1.0, 1.0), (Key::Media(MediaKey::Forward), 23.0, 0.0, 1.0, 1.0),
+    (Key::Gaming(GamingKey::G1), 0.0, 2.75, 1.0, 1.0), (Key::Gaming(GamingKey::G2), 0.0, 3.75, 1.0, 1.0),
+    (Key::Gaming(GamingKey::G3), 0.0, 4.75, 1.0, 1.0), (Key::Gaming(GamingKey::G4), 0.0, 5.75, 1.0, 1.0),
+    (Key::Gaming(GamingKey::G5), 0.0, 6.75, 1.0, 1.0),
+];
+
+/// Returns whether the key has an LED, which all keys but the media keys have.
+pub fn is_lightable(key: &Key) -> bool {
+    match key {
+        &Key::Media(_) => false,
+        _ => true,
+    }
+}
+
+/// The physical layout variant of the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variant {
+    /// Tall Return key, with Sharp left of it and SmallerThan right of LeftShift.
+    Iso,
+    /// Wide Return key with Pipe above it and a wide LeftShift.
+    Ansi,
+}
+
+/// Position and size of a key in millimetres.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyGeometry {
+    pub key: Key,
+    /// Left edge.
+    pub x: f64,
+    /// Top edge.
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl KeyGeometry {
+    fn from_units(key: Key, (x, y, w, h): (f64, f64, f64, f64)) -> KeyGeometry {
+        KeyGeometry {
+            key: key,
+            x: x * KEY_PITCH,
+            y: y * KEY_PITCH,
+            width: w * KEY_PITCH,
+            height: h * KEY_PITCH,
+        }
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    /// Returns whether the point lies on the key.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    /// Returns the distance between the centers of the keys.
+    pub fn distance(&self, other: &KeyGeometry) -> f64 {
+        let (x, y) = other.center();
+        self.distance_to(x, y)
+    }
+
+    /// Returns the distance between the center of the key and the point.
+    pub fn distance_to(&self, x: f64, y: f64) -> f64 {
+        let (cx, cy) = self.center();
+        ((x - cx).powi(2) + (y - cy).powi(2)).sqrt()
+    }
+}
+
+/// The positions of all keys of a G910, the standard and numpad keys, the G- and M-keys, the
+/// media keys and the logos.
+#[derive(Debug, Clone)]
+pub struct Geometry {
+    variant: Variant,
+    keys: Vec<KeyGeometry>,
+    /// Top edges of the rows of the standard keys.
+    rows: Vec<f64>,
+}
+
+impl Geometry {
+    pub fn new(variant: Variant) -> Geometry {
+        let (dx, dy) = STANDARD_OFFSET;
+        let mut keys: Vec<_> = ISO.iter()
+            .filter(|&&(key, _, _, _, _)| variant == Variant::Iso || (key != K::Sharp && key != K::SmallerThan))
+            .map(|&(key, x, y, w, h)| match variant {
+                Variant::Ansi => ANSI.iter().find(|&&(k, _, _, _, _)| k == key)
+                    .map_or((key, x, y, w, h), |&entry| entry),
+                Variant::Iso => (key, x, y, w, h),
+            })
+            .map(|(key, x, y, w, h)| KeyGeometry::from_units(Key::Standard(key), (x + dx, y + dy, w, h)))
+            .collect();
+        if variant == Variant::Ansi {
+            let &(key, x, y, w, h) = ANSI.iter().find(|&&(k, _, _, _, _)| k == K::Pipe).unwrap();
+            keys.push(KeyGeometry::from_units(Key::Standard(key), (x + dx, y + dy, w, h)));
+        }
+        let mut rows: Vec<f64> = Vec::new();
+        for key in &keys {
+            if !rows.iter().any(|&y| (y - key.y).abs() < 0.01) {
+                rows.push(key.y);
+            }
+        }
+        rows.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        keys.extend(OTHER.iter().map(|&(ref key, x, y, w, h)| KeyGeometry::from_units(key.clone(), (x, y, w, h))));
+        Geometry {
+            variant: variant,
+            keys: keys,
+            rows: rows,
+        }
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    pub fn keys(&self) -> &[KeyGeometry] {
+        &self.keys
+    }
+
+    /// Returns the keys with an LED, i.e. all but the media keys, with their centers.
+    pub fn lightable(&self) -> Vec<(Key, (f64, f64))> {
+        self.keys.iter()
+            .filter(|g| is_lightable(&g.key))
+            .map(|g| (g.key.clone(), g.center()))
+            .collect()
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&KeyGeometry> {
+        self.keys.iter().find(|g| g.key == *key)
+    }
+
+    pub fn center(&self, key: &Key) -> Option<(f64, f64)> {
+        self.get(key).map(KeyGeometry::center)
+    }
+
+    /// Returns the width and height of the area covered by keys.
+    pub fn size(&self) -> (f64, f64) {
+        let width = self.keys.iter().map(|g| g.x + g.width).fold(0.0, f64::max);
+        let height = self.keys.iter().map(|g| g.y + g.height).fold(0.0, f64::max);
+        (width, height)
+    }
+
+    /// Returns the key at the given point.
+    pub fn key_at(&self, x: f64, y: f64) -> Option<&KeyGeometry> {
+        self.keys.iter().find(|g| g.contains(x, y))
+    }
+
+    /// Returns the key whose center is nearest to the given point among the keys matching the
+    /// filter.
+    pub fn nearest<F: Fn(&KeyGeometry) -> bool>(&self, x: f64, y: f64, filter: F) -> Option<&KeyGeometry> {
+        self.keys.iter()
+            .filter(|g| filter(g))
+            .min_by(|a, b| a.distance_to(x, y).partial_cmp(&b.distance_to(x, y)).unwrap_or(Ordering::Equal))
+    }
+
+    /// Returns the other keys sorted by the distance of their center to the given key's.
+    pub fn neighbours(&self, key: &Key) -> Vec<&KeyGeometry> {
+        let origin = match self.get(key) {
+            Some(origin) => origin,
+            None => return Vec::new(),
+        };
+        let mut neighbours: Vec<_> = self.keys.iter().filter(|g| g.key != *key).collect();
+        neighbours.sort_by(|a, b| origin.distance(a).partial_cmp(&origin.distance(b)).unwrap_or(Ordering::Equal));
+        neighbours
+    }
+
+    /// Returns the number of rows of standard keys, the function keys being row 0 and the
+    /// space bar row 5.
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns the keys of the given row sorted from left to right, including the G-keys next
+    /// to it and keys spanning multiple rows like the numpad's Plus.
+    pub fn row(&self, row: usize) -> Vec<&KeyGeometry> {
+        let y = match self.rows.get(row) {
+            Some(&y) => y + KEY_PITCH / 2.0,
+            None => return Vec::new(),
+        };
+        let mut keys: Vec<_> = self.keys.iter().filter(|g| g.y <= y && y < g.y + g.height).collect();
+        keys.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap_or(Ordering::Equal));
+        keys
+    }
+
+    /// Returns the row of the top edge of the given key.
+    pub fn row_of(&self, key: &Key) -> Option<usize> {
+        let g = match self.get(key) {
+            Some(g) => g,
+            None => return None,
+        };
+        self.rows.iter().position(|&y| (y - g.y).abs() < 0.01)
+    }
+
+    /// Returns the keys covering the given horizontal position sorted from top to bottom.
+    pub fn column(&self, x: f64) -> Vec<&KeyGeometry> {
+        let mut keys: Vec<_> = self.keys.iter().filter(|g| g.x <= x && x < g.x + g.width).collect();
+        keys.sort_by(|a, b| a.y.partial_cmp(&b.y).unwrap_or(Ordering::Equal));
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use g910::*;
+
+    use super::{Geometry, KEY_PITCH, Variant};
+
+    fn key(key: StandardKey) -> Key {
+        Key::Standard(key)
+    }
+
+    fn assert_near((x, y): (f64, f64), (ex, ey): (f64, f64)) {
+        assert!((x - ex).abs() < 1e-9 && (y - ey).abs() < 1e-9, "{:?} != {:?}", (x, y), (ex, ey));
+    }
+
+    #[test]
+    fn positions_keys() {
+        let geometry = Geometry::new(Variant::Iso);
+        assert_near(geometry.center(&key(StandardKey::Esc)).unwrap(), (2.0 * KEY_PITCH, 1.75 * KEY_PITCH));
+        assert_near(geometry.center(&key(StandardKey::F)).unwrap(), (6.75 * KEY_PITCH, 5.25 * KEY_PITCH));
+        assert_near(geometry.center(&Key::Gaming(GamingKey::G1)).unwrap(), (0.5 * KEY_PITCH, 3.25 * KEY_PITCH));
+        let (x, y) = geometry.center(&key(StandardKey::Space)).unwrap();
+        assert_eq!(geometry.key_at(x, y).map(|g| g.key.clone()), Some(key(StandardKey::Space)));
+    }
+
+    #[test]
+    fn finds_nearest_key() {
+        let geometry = Geometry::new(Variant::Iso);
+        let (x, y) = geometry.center(&key(StandardKey::A)).unwrap();
+        assert_eq!(geometry.nearest(x, y, |_| true).unwrap().key, key(StandardKey::A));
+        let other = geometry.nearest(x, y, |g| g.key != key(StandardKey::A)).unwrap();
+        assert_eq!(other.key, key(StandardKey::S));
+        assert_eq!(geometry.neighbours(&key(StandardKey::A))[0].key, key(StandardKey::S));
+    }
+
+    #[test]
+    fn splits_keys_into_rows_and_columns() {
+        let geometry = Geometry::new(Variant::Iso);
+        assert_eq!(geometry.row_count(), 6);
+        let top: Vec<_> = geometry.row(0).into_iter().map(|g| g.key.clone()).collect();
+        assert_eq!(top.len(), 16);
+        assert_eq!(top[0], key(StandardKey::Esc));
+        assert_eq!(top[15], key(StandardKey::Pause));
+        // the G-keys and keys spanning two rows are part of the row
+        let home: Vec<_> = geometry.row(3).into_iter().map(|g| g.key.clone()).collect();
+        assert_eq!(home[0], Key::Gaming(GamingKey::G3));
+        assert!(home.contains(&key(StandardKey::Return)));
+        assert!(home.contains(&key(StandardKey::NumPlus)));
+        assert!(geometry.row(6).is_empty());
+        assert_eq!(geometry.row_of(&key(StandardKey::Q)), Some(2));
+        assert_eq!(geometry.row_of(&Key::Logo(LogoKey::G)), None);
+
+        let (x, _) = geometry.center(&key(StandardKey::Esc)).unwrap();
+        let column: Vec<_> = geometry.column(x).into_iter().map(|g| g.key.clone()).collect();
+        assert_eq!(column, vec![Key::Gaming(GamingKey::M1), key(StandardKey::Esc), key(StandardKey::Circumflex),
+            key(StandardKey::Tab), key(StandardKey::CapsLock), key(StandardKey::LeftShift),
+            key(StandardKey::LeftControl)]);
+    }
+
+    #[test]
+    fn differs_between_iso_and_ansi() {
+        let iso = Geometry::new(Variant::Iso);
+        let ansi = Geometry::new(Variant::Ansi);
+        let iso_return = iso.get(&key(StandardKey::Return)).unwrap();
+        let ansi_return = ansi.get(&key(StandardKey::Return)).unwrap();
+        assert_eq!((iso_return.width, iso_return.height), (1.25 * KEY_PITCH, 2.0 * KEY_PITCH));
+        assert_eq!((ansi_return.width, ansi_return.height), (2.25 * KEY_PITCH, KEY_PITCH));
+        assert_eq!(iso.row_of(&key(StandardKey::Return)), Some(2));
+        assert_eq!(ansi.row_of(&key(StandardKey::Return)), Some(3));
+        for k in &[StandardKey::Sharp, StandardKey::SmallerThan] {
+            assert!(iso.get(&key(*k)).is_some());
+            assert!(ansi.get(&key(*k)).is_none());
+        }
+        assert!(iso.get(&key(StandardKey::Pipe)).is_none());
+        assert!(ansi.get(&key(StandardKey::Pipe)).is_some());
+        assert_eq!(ansi.keys().len(), iso.keys().len() - 1);
+    }
+}
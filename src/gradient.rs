@@ -0,0 +1,221 @@
+use g910::Color;
+
+/// Color space in which a gradient blends between two neighbouring stops.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    /// Blends each of red, green and blue linearly.
+    Rgb,
+    /// Blends hue, saturation and value linearly, taking the shorter way around the hue circle.
+    Hsv,
+}
+
+/// Maps values from 0 to 1 to colors.
+///
+/// A gradient consists of an arbitrary number of color stops, each at a position between 0 and 1.
+/// Values between two stops are interpolated, values before the first or after the last stop get
+/// the color of that stop.
+#[derive(Debug, Clone)]
+pub struct Gradient {
+    stops: Vec<(f64, Color)>,
+    interpolation: Interpolation,
+}
+
+impl Gradient {
+    /// Creates a gradient without stops, which is black everywhere until stops are added.
+    pub fn new(interpolation: Interpolation) -> Gradient {
+        Gradient {
+            stops: Vec::new(),
+            interpolation: interpolation,
+        }
+    }
+
+    /// Creates a gradient with the given colors evenly distributed from 0 to 1.
+    pub fn evenly(colors: &[Color], interpolation: Interpolation) -> Gradient {
+        let mut gradient = Gradient::new(interpolation);
+        let steps = if colors.len() > 1 { (colors.len() - 1) as f64 } else { 1.0 };
+        for (i, color) in colors.iter().enumerate() {
+            gradient = gradient.stop(i as f64 / steps, *color);
+        }
+        gradient
+    }
+
+    /// Six Color Gradient:
+    /// (1) black, (2) blue, (3) cyan, (4) green, (5) yellow, (6) red
+    /// (http://www.andrewnoske.com/wiki/Code_-_heatmaps_and_color_gradients)
+    pub fn heatmap() -> Gradient {
+        Gradient::evenly(&[
+            Color::new(0, 0, 0),
+            Color::new(0, 0, 255),
+            Color::new(0, 255, 255),
+            Color::new(0, 255, 0),
+            Color::new(255, 255, 0),
+            Color::new(255, 0, 0),
+        ], Interpolation::Rgb)
+    }
+
+    /// Adds a color stop at the given position, which is clamped to [0, 1].
+    pub fn stop(mut self, position: f64, color: Color) -> Gradient {
+        let position = position.max(0.0).min(1.0);
+        let idx = self.stops.iter().position(|&(p, _)| p > position).unwrap_or(self.stops.len());
+        self.stops.insert(idx, (position, color));
+        self
+    }
+
+    pub fn interpolation(mut self, interpolation: Interpolation) -> Gradient {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn stops(&self) -> &[(f64, Color)] {
+        &self.stops
+    }
+
+    /// Returns the color at the given position.
+    pub fn color_at(&self, value: f64) -> Color {
+        let first = match self.stops.first() {
+            Some(&first) => first,
+            None => return Color::new(0, 0, 0),
+        };
+        if value.is_nan() || value <= first.0 {
+            return first.1;
+        }
+        for window in self.stops.windows(2) {
+            let (start, from) = window[0];
+            let (end, to) = window[1];
+            if value < end {
+                let diff = (value - start) / (end - start);
+                return match self.interpolation {
+                    Interpolation::Rgb => lerp_rgb(from, to, diff),
+                    Interpolation::Hsv => lerp_hsv(from, to, diff),
+                };
+            }
+        }
+        self.stops[self.stops.len()-1].1
+    }
+}
+
+fn lerp_rgb(from: Color, to: Color, diff: f64) -> Color {
+    let lerp = |a: u8, b: u8| ((((b as i16 - a as i16) as f64) * diff) as i16 + a as i16) as u8;
+    Color::new(
+        lerp(from.red, to.red),
+        lerp(from.green, to.green),
+        lerp(from.blue, to.blue),
+    )
+}
+
+fn lerp_hsv(from: Color, to: Color, diff: f64) -> Color {
+    let (h1, s1, v1) = to_hsv(from);
+    let (mut h2, s2, v2) = to_hsv(to);
+    // take the shorter way around the hue circle
+    if h2 - h1 > 180.0 {
+        h2 -= 360.0;
+    } else if h1 - h2 > 180.0 {
+        h2 += 360.0;
+    }
+    from_hsv(
+        h1 + (h2 - h1) * diff,
+        s1 + (s2 - s1) * diff,
+        v1 + (v2 - v1) * diff,
+    )
+}
+
+/// Converts a color to hue (0 to 360), saturation and value (both 0 to 1).
+pub fn to_hsv(color: Color) -> (f64, f64, f64) {
+    let r = color.red as f64 / 255.0;
+    let g = color.green as f64 / 255.0;
+    let b = color.blue as f64 / 255.0;
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * (((g - b) / delta) % 6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let hue = if hue < 0.0 { hue + 360.0 } else { hue };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    (hue, saturation, max)
+}
+
+/// Converts hue (in degrees, wrapped into 0 to 360), saturation and value (both clamped to
+/// [0, 1]) to a color.
+pub fn from_hsv(hue: f64, saturation: f64, value: f64) -> Color {
+    let hue = ((hue % 360.0) + 360.0) % 360.0;
+    let saturation = saturation.max(0.0).min(1.0);
+    let value = value.max(0.0).min(1.0);
+    let c = value * saturation;
+    let x = c * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let m = value - c;
+    let (r, g, b) = match (hue / 60.0) as u8 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    Color::new(
+        ((r + m) * 255.0).round() as u8,
+        ((g + m) * 255.0).round() as u8,
+        ((b + m) * 255.0).round() as u8,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64;
+    use g910::Color;
+
+    use super::{Gradient, Interpolation};
+
+    #[test]
+    fn takes_colors_of_stops() {
+        let gradient = Gradient::heatmap();
+        assert_eq!(gradient.color_at(0.0), Color::new(0, 0, 0));
+        assert_eq!(gradient.color_at(0.2), Color::new(0, 0, 255));
+        assert_eq!(gradient.color_at(0.6), Color::new(0, 255, 0));
+        assert_eq!(gradient.color_at(1.0), Color::new(255, 0, 0));
+        // values outside of the stops get the color of the nearest one
+        assert_eq!(gradient.color_at(-1.0), Color::new(0, 0, 0));
+        assert_eq!(gradient.color_at(2.0), Color::new(255, 0, 0));
+        assert_eq!(gradient.color_at(f64::NAN), Color::new(0, 0, 0));
+        assert_eq!(Gradient::new(Interpolation::Rgb).color_at(0.5), Color::new(0, 0, 0));
+    }
+
+    #[test]
+    fn interpolates_between_stops() {
+        let gradient = Gradient::new(Interpolation::Rgb)
+            .stop(0.5, Color::new(200, 100, 0))
+            .stop(0.0, Color::new(0, 0, 0));
+        assert_eq!(gradient.color_at(0.25), Color::new(100, 50, 0));
+        assert_eq!(gradient.color_at(0.75), Color::new(200, 100, 0));
+    }
+
+    #[test]
+    fn jumps_at_coincident_stops() {
+        let gradient = Gradient::new(Interpolation::Rgb)
+            .stop(0.0, Color::new(0, 0, 0))
+            .stop(0.5, Color::new(255, 0, 0))
+            .stop(0.5, Color::new(0, 0, 255))
+            .stop(1.0, Color::new(0, 0, 0));
+        assert_eq!(gradient.color_at(0.25), Color::new(127, 0, 0));
+        // the stop added last wins
+        assert_eq!(gradient.color_at(0.5), Color::new(0, 0, 255));
+        assert_eq!(gradient.color_at(0.75), Color::new(0, 0, 128));
+    }
+
+    #[test]
+    fn wraps_hue_the_short_way() {
+        let (red, blue) = (Color::new(255, 0, 0), Color::new(0, 0, 255));
+        let rgb = Gradient::evenly(&[red, blue], Interpolation::Rgb);
+        assert_eq!(rgb.color_at(0.5), Color::new(128, 0, 127));
+        // from hue 0 to 240 via magenta at 300 instead of green at 120
+        let hsv = rgb.interpolation(Interpolation::Hsv);
+        assert_eq!(hsv.color_at(0.5), Color::new(255, 0, 255));
+        assert_eq!(Gradient::evenly(&[blue, red], Interpolation::Hsv).color_at(0.5), Color::new(255, 0, 255));
+    }
+}
@@ -0,0 +1,151 @@
+use std::io::{self, Write};
+use g910::*;
+
+use geometry::{Geometry, Variant, KEY_PITCH};
+use super::Heatmap;
+
+/// Size of one key unit in the svg rendering in pixels.
+const UNIT: f64 = 40.0;
+
+impl Heatmap {
+    /// Writes one `key,count,intensity` line per key, preceded by a header line.
+    ///
+    /// The intensity is the count scaled to [0, 1] as used for the colors.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        try!(writeln!(writer, "key,count,intensity"));
+        for (key, count, intensity) in self.intensities() {
+            try!(writeln!(writer, "{:?},{},{:.4}", key, count, intensity));
+        }
+        Ok(())
+    }
+
+    /// Writes an array of `{"key", "count", "intensity"}` objects, one per key.
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let intensities = self.intensities();
+        try!(writeln!(writer, "["));
+        for (i, (key, count, intensity)) in intensities.iter().enumerate() {
+            let sep = if i + 1 < intensities.len() { "," } else { "" };
+            try!(writeln!(writer, "  {{\"key\": \"{:?}\", \"count\": {}, \"intensity\": {:.4}}}{}",
+                key, count, intensity, sep));
+        }
+        writeln!(writer, "]")
+    }
+
+    /// Renders the keys of the ISO keyboard as svg, filled with the same colors as `colors`.
+    pub fn write_svg<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_svg_with(&Geometry::new(Variant::Iso), writer)
+    }
+
+    /// Renders the keys of the given geometry as svg, filled with the same colors as `colors`.
+    pub fn write_svg_with<W: Write>(&self, geometry: &Geometry, mut writer: W) -> io::Result<()> {
+        let intensities = self.intensities();
+        let scale = UNIT / KEY_PITCH;
+        let (width, height) = geometry.size();
+        try!(writeln!(writer, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">",
+            width * scale, height * scale));
+        try!(writeln!(writer, "  <rect width=\"100%\" height=\"100%\" fill=\"#202020\"/>"));
+        for g in geometry.keys() {
+            let (count, intensity) = match intensities.iter().find(|&&(ref k, _, _)| *k == g.key) {
+                Some(&(_, count, intensity)) => (count, intensity),
+                Option::None => continue,
+            };
+            let color = self.gradient().color_at(intensity);
+            // keep the label readable on bright keys
+            let luminance = 0.299 * color.red as f64 + 0.587 * color.green as f64 + 0.114 * color.blue as f64;
+            let text = if luminance > 128.0 { "black" } else { "white" };
+            let (cx, cy) = g.center();
+            let label = label(&g.key);
+            try!(writeln!(writer, "  <g>"));
+            try!(writeln!(writer, "    <title>{}: {}</title>", label, count));
+            try!(writeln!(writer, "    <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"4\" fill=\"#{:02x}{:02x}{:02x}\"/>",
+                g.x * scale + 2.0, g.y * scale + 2.0, g.width * scale - 4.0, g.height * scale - 4.0,
+                color.red, color.green, color.blue));
+            try!(writeln!(writer, "    <text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"9\" text-anchor=\"middle\" fill=\"{}\">{}</text>",
+                cx * scale, cy * scale + 3.0, text, label));
+            try!(writeln!(writer, "  </g>"));
+        }
+        writeln!(writer, "</svg>")
+    }
+}
+
+/// Returns the name of the key without its kind.
+fn label(key: &Key) -> String {
+    match key {
+        &Key::Standard(k) => format!("{:?}", k),
+        &Key::Gaming(k) => format!("{:?}", k),
+        &Key::Logo(k) => format!("{:?}", k),
+        &Key::Media(k) => format!("{:?}", k),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use g910::*;
+
+    use geometry::{Geometry, Variant};
+    use super::super::Heatmap;
+
+    fn heatmap() -> Heatmap {
+        let mut heatmap = Heatmap::new();
+        heatmap.increment(&Key::Standard(StandardKey::A));
+        heatmap.increment(&Key::Standard(StandardKey::A));
+        heatmap.increment(&Key::Standard(StandardKey::B));
+        heatmap
+    }
+
+    fn output<F: Fn(&Heatmap, &mut Vec<u8>)>(write: F) -> Vec<String> {
+        let mut out = Vec::new();
+        write(&heatmap(), &mut out);
+        String::from_utf8(out).unwrap().lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn writes_csv() {
+        let lines = output(|h, out| h.write_csv(out).unwrap());
+        assert_eq!(lines.len(), Key::values().len() + 1);
+        assert_eq!(lines[0], "key,count,intensity");
+        assert_eq!(lines[1], "Gaming(G1),0,0.0000");
+        assert!(lines.contains(&"Standard(A),2,1.0000".to_string()));
+        assert!(lines.contains(&"Standard(B),1,0.5000".to_string()));
+        assert!(lines.contains(&"Standard(C),0,0.0000".to_string()));
+    }
+
+    #[test]
+    fn writes_json() {
+        let lines = output(|h, out| h.write_json(out).unwrap());
+        assert_eq!(lines.len(), Key::values().len() + 2);
+        assert_eq!(lines[0], "[");
+        assert_eq!(lines[1], "  {\"key\": \"Gaming(G1)\", \"count\": 0, \"intensity\": 0.0000},");
+        assert!(lines.contains(&"  {\"key\": \"Standard(A)\", \"count\": 2, \"intensity\": 1.0000},".to_string()));
+        assert!(lines.contains(&"  {\"key\": \"Standard(B)\", \"count\": 1, \"intensity\": 0.5000},".to_string()));
+        // no separator after the last object
+        assert_eq!(lines[lines.len() - 2], "  {\"key\": \"Standard(_9)\", \"count\": 0, \"intensity\": 0.0000}");
+        assert_eq!(lines[lines.len() - 1], "]");
+    }
+
+    #[test]
+    fn fills_svg_keys_with_their_colors() {
+        let heatmap = heatmap();
+        let geometry = Geometry::new(Variant::Iso);
+        let mut out = Vec::new();
+        heatmap.write_svg_with(&geometry, &mut out).unwrap();
+        let fills: Vec<_> = String::from_utf8(out).unwrap().lines()
+            .filter(|l| l.trim().starts_with("<rect x="))
+            .map(|l| l.rsplitn(2, "fill=\"").next().unwrap()[..7].to_string())
+            .collect();
+        assert_eq!(fills.len(), geometry.keys().len());
+        let colors: HashMap<_, _> = heatmap.colors().into_iter()
+            .map(|kc| (kc.key, format!("#{:02x}{:02x}{:02x}", kc.color.red, kc.color.green, kc.color.blue)))
+            .collect();
+        let mut checked = 0;
+        for (g, fill) in geometry.keys().iter().zip(&fills) {
+            if let Some(color) = colors.get(&g.key) {
+                assert_eq!(fill, color, "{:?}", g.key);
+                checked += 1;
+            }
+        }
+        assert!(checked > 100);
+        assert!(fills.iter().any(|f| f != "#000000"));
+    }
+}
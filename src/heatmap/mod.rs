@@ -0,0 +1,642 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use libusb::Result as UsbResult;
+use g910::*;
+
+use backend::{self, KeyboardHandler, Leds};
+use error::Error;
+use geometry;
+use gradient::Gradient;
+
+pub use self::stats::{Finger, Stats};
+
+mod export;
+mod stats;
+
+/// Seconds between two writes of the counts file.
+const CHECKPOINT_INTERVAL: u64 = 60;
+/// Milliseconds between two redraws of a decaying heatmap.
+const DECAY_INTERVAL: u64 = 100;
+/// Heat at which a key of a decaying heatmap is rendered red.
+const DECAY_SATURATION: f64 = 8.0;
+
+/// What the heatmap shows on the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum View {
+    /// How often each key has been pressed, decaying if enabled.
+    PressCount,
+    /// How long each key has been held on average during the session.
+    HoldTime,
+    /// How many bigrams of the session start with each key.
+    BigramStart,
+}
+
+struct Decay {
+    half_life: Duration,
+    last: Option<Instant>,
+}
+
+pub struct HeatmapHandler {
+     heatmap: Heatmap,
+     path: Option<PathBuf>,
+     dirty: bool,
+     last_checkpoint: Option<Instant>,
+     decay: Option<Decay>,
+     stats: Arc<Mutex<Stats>>,
+     view: Arc<Mutex<View>>,
+     on_error: Option<Box<FnMut(Error)>>,
+}
+
+impl HeatmapHandler {
+    pub fn new() -> HeatmapHandler {
+        HeatmapHandler {
+            heatmap: Heatmap::new(),
+            path: None,
+            dirty: false,
+            last_checkpoint: None,
+            decay: None,
+            stats: Arc::new(Mutex::new(Stats::new())),
+            view: Arc::new(Mutex::new(View::PressCount)),
+            on_error: None,
+        }
+    }
+
+    /// Creates a HeatmapHandler which keeps its counts in the given file.
+    ///
+    /// Existing counts are loaded on init. The file is rewritten every minute if keys have been
+    /// pressed since the last write and once more when the handler is dropped.
+    pub fn with_file<P: Into<PathBuf>>(path: P) -> HeatmapHandler {
+        let mut handler = HeatmapHandler::new();
+        handler.path = Some(path.into());
+        handler
+    }
+
+    /// Lets the displayed heat of each key fade with the given half-life.
+    ///
+    /// The keyboard then shows what has been typed recently instead of all-time counts: keys
+    /// cool back to black when they haven't been pressed for a while. The counts themselves
+    /// keep accumulating and are still persisted.
+    pub fn decaying(mut self, half_life: Duration) -> HeatmapHandler {
+        self.heatmap.set_saturation(DECAY_SATURATION);
+        self.decay = Some(Decay {
+            half_life: half_life,
+            last: None,
+        });
+        self
+    }
+
+    /// Colors keys with the given gradient instead of `Gradient::heatmap()`.
+    pub fn gradient(mut self, gradient: Gradient) -> HeatmapHandler {
+        self.heatmap.set_gradient(gradient);
+        self
+    }
+
+    /// Maps counts onto the gradient with the given scaling instead of `Scaling::Linear`.
+    pub fn scaling(mut self, scaling: Scaling) -> HeatmapHandler {
+        self.heatmap.set_scaling(scaling);
+        self
+    }
+
+    /// Shows the given view instead of `View::PressCount`.
+    pub fn view(self, view: View) -> HeatmapHandler {
+        *self.view.lock().unwrap() = view;
+        self
+    }
+
+    /// Returns a handle to the view shown on the keyboard, which can be switched while the
+    /// handler is running. The new view is drawn with the next key event.
+    pub fn view_handle(&self) -> Arc<Mutex<View>> {
+        self.view.clone()
+    }
+
+    /// Calls the function with errors of the counts file, which can't be loaded or saved. They
+    /// are ignored by default.
+    ///
+    /// A file which can't be loaded is never overwritten, the counts of the session aren't
+    /// persisted then.
+    pub fn on_error<F: FnMut(Error) + 'static>(mut self, f: F) -> HeatmapHandler {
+        self.on_error = Some(Box::new(f));
+        self
+    }
+
+    /// Returns a handle to the typing statistics of this session, which can be queried while
+    /// the handler is running.
+    pub fn stats(&self) -> Arc<Mutex<Stats>> {
+        self.stats.clone()
+    }
+
+    fn colors(&self) -> Vec<KeyColor> {
+        let view = *self.view.lock().unwrap();
+        let stats = self.stats.lock().unwrap();
+        let values = match view {
+            View::PressCount => return self.heatmap.colors(),
+            View::HoldTime => stats.average_holds().into_iter().map(|(k, v)| (k, secs(v))).collect(),
+            View::BigramStart => stats.bigram_starts().into_iter().map(|(k, v)| (k, v as f64)).collect(),
+        };
+        self.heatmap.colors_for(&values)
+    }
+
+    fn load(&mut self) {
+        let heatmap = match self.path {
+            Some(ref path) => Heatmap::load(path),
+            None => return,
+        };
+        match heatmap {
+            Ok(mut heatmap) => {
+                heatmap.set_saturation(self.heatmap.saturation);
+                heatmap.set_gradient(self.heatmap.gradient.clone());
+                heatmap.set_scaling(self.heatmap.scaling);
+                if self.decay.is_some() {
+                    // old presses aren't recent activity
+                    heatmap.decay(0.0);
+                }
+                self.heatmap = heatmap;
+            },
+            // first start, nothing has been saved yet
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => {
+                // don't overwrite a file we couldn't read
+                self.path = None;
+                self.report(e.into());
+            }
+        }
+    }
+
+    fn checkpoint(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        let result = match self.path {
+            Some(ref path) => self.heatmap.save(path),
+            None => Ok(()),
+        };
+        match result {
+            Ok(()) => self.dirty = false,
+            Err(e) => self.report(e.into()),
+        }
+    }
+
+    fn report(&mut self, err: Error) {
+        if let Some(ref mut on_error) = self.on_error {
+            on_error(err);
+        }
+    }
+}
+
+impl KeyboardHandler for HeatmapHandler {
+    fn init(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        try!(keyboard.set_all_colors(Color::new(0, 0, 0)));
+        if self.path.is_none() {
+            return Ok(());
+        }
+        self.load();
+        keyboard.set_key_colors(self.colors())
+    }
+
+    fn redraw(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        keyboard.set_key_colors(self.colors())
+    }
+
+    fn accept_key(&self, evt: &KeyEvent) -> bool {
+        match evt {
+            &KeyEvent::KeyPressed(_) => true,
+            &KeyEvent::KeyReleased(_) => true,
+        }
+    }
+
+    fn handle_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        match evt {
+            &KeyEvent::KeyPressed(ref key) => {
+                self.heatmap.increment(key);
+                self.dirty = true;
+                self.stats.lock().unwrap().press(key, now);
+            },
+            &KeyEvent::KeyReleased(ref key) => {
+                self.stats.lock().unwrap().release(key, now);
+                // only hold times change on release
+                if *self.view.lock().unwrap() != View::HoldTime {
+                    return Ok(());
+                }
+            },
+        }
+        keyboard.set_key_colors(self.colors())
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        match self.decay {
+            Some(_) => Some(Duration::from_millis(DECAY_INTERVAL)),
+            None => Some(Duration::from_secs(CHECKPOINT_INTERVAL)),
+        }
+    }
+
+    fn handle_time(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        // the first tick is already a minute in without decay
+        let due = self.last_checkpoint
+            .map_or(true, |last| now.duration_since(last) >= Duration::from_secs(CHECKPOINT_INTERVAL));
+        if due {
+            self.checkpoint();
+            self.last_checkpoint = Some(now);
+        }
+        let factor = match self.decay {
+            Some(ref mut decay) => {
+                let last = *decay.last.get_or_insert(now);
+                decay.last = Some(now);
+                0.5f64.powf(secs(now.duration_since(last)) / secs(decay.half_life))
+            },
+            None => return Ok(()),
+        };
+        let changed = self.heatmap.decay(factor);
+        if changed && *self.view.lock().unwrap() == View::PressCount {
+            keyboard.set_key_colors(self.colors())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for HeatmapHandler {
+    fn drop(&mut self) {
+        self.checkpoint();
+    }
+}
+
+impl From<HeatmapHandler> for Handler {
+    fn from(handler: HeatmapHandler) -> Handler {
+        backend::into_handler(handler)
+    }
+}
+
+/// How counts are mapped onto the gradient.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scaling {
+    /// Relative to the hottest key.
+    Linear,
+    /// Logarithmic relative to the hottest key, so single heavily used keys like Space don't
+    /// push all other keys to the cold end of the gradient.
+    Logarithmic,
+    /// By percentile among all pressed keys: the hottest key is at the hot end, the coldest
+    /// pressed key at 1/n, keys which haven't been pressed at the cold end.
+    Rank,
+}
+
+pub struct Heatmap {
+    data: HashMap<Key, u64>,
+    heat: HashMap<Key, f64>,
+    saturation: f64,
+    gradient: Gradient,
+    scaling: Scaling,
+}
+
+impl Heatmap {
+    pub fn new() -> Heatmap {
+        let data: HashMap<_, _> = Key::values().into_iter().map(|k| (k, 0)).collect();
+        let heat = data.keys().map(|k| (k.clone(), 0.0)).collect();
+        Heatmap {
+            data: data,
+            heat: heat,
+            saturation: 0.0,
+            gradient: Gradient::heatmap(),
+            scaling: Scaling::Linear,
+        }
+    }
+
+    /// Loads counts previously written with `save`.
+    ///
+    /// Keys missing in the file start at zero, unknown keys and malformed lines are skipped.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Heatmap> {
+        let file = try!(File::open(path));
+        Heatmap::read_from(BufReader::new(file))
+    }
+
+    pub fn read_from<R: BufRead>(reader: R) -> io::Result<Heatmap> {
+        let mut heatmap = Heatmap::new();
+        let names: HashMap<String, Key> = heatmap.data.keys()
+            .map(|k| (format!("{:?}", k), k.clone()))
+            .collect();
+        for line in reader.lines() {
+            let line = try!(line);
+            let mut split = line.trim().rsplitn(2, ' ');
+            let count = match split.next().map(|c| c.parse::<u64>()) {
+                Some(Ok(count)) => count,
+                _ => continue,
+            };
+            let key = match split.next().and_then(|name| names.get(name.trim())) {
+                Some(key) => key,
+                None => continue,
+            };
+            heatmap.data.insert(key.clone(), count);
+            heatmap.heat.insert(key.clone(), count as f64);
+        }
+        Ok(heatmap)
+    }
+
+    /// Writes all counts to the given file, one `<key> <count>` pair per line.
+    ///
+    /// The counts are written to a temporary file first, which is then renamed, so an
+    /// interrupted write never leaves a truncated file behind.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        {
+            let mut writer = BufWriter::new(try!(File::create(&tmp)));
+            try!(self.write_to(&mut writer));
+            try!(writer.flush());
+        }
+        fs::rename(&tmp, path)
+    }
+
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut lines: Vec<_> = self.data.iter()
+            .map(|(k, v)| format!("{:?} {}", k, v))
+            .collect();
+        lines.sort();
+        for line in lines {
+            try!(writeln!(writer, "{}", line));
+        }
+        Ok(())
+    }
+
+    /// Counts a press of the given key.
+    ///
+    /// All keys are counted, including media keys and keys unknown to `Key::values()`, even
+    /// though only lightable ones are returned by `colors`.
+    pub fn increment(&mut self, key: &Key) {
+        *self.data.entry(key.clone()).or_insert(0) += 1;
+        *self.heat.entry(key.clone()).or_insert(0.0) += 1.0;
+    }
+
+    /// Scales the heat of all keys by the given factor without touching their counts.
+    ///
+    /// Heat below 0.01 is cut to zero, so keys which haven't been pressed for long enough end up
+    /// black. Returns whether any key had heat left before decaying.
+    pub fn decay(&mut self, factor: f64) -> bool {
+        let mut changed = false;
+        for heat in self.heat.values_mut() {
+            if *heat == 0.0 {
+                continue;
+            }
+            changed = true;
+            *heat *= factor;
+            if *heat < 0.01 {
+                *heat = 0.0;
+            }
+        }
+        changed
+    }
+
+    /// Sets the heat at which a key is colored with the hottest color even if no other key is
+    /// hotter. With the default of zero, colors are scaled against the hottest key.
+    pub fn set_saturation(&mut self, saturation: f64) {
+        self.saturation = saturation;
+    }
+
+    /// Sets the gradient used to color keys, defaults to `Gradient::heatmap()`.
+    pub fn set_gradient(&mut self, gradient: Gradient) {
+        self.gradient = gradient;
+    }
+
+    /// Sets how counts are mapped onto the gradient, defaults to `Scaling::Linear`.
+    pub fn set_scaling(&mut self, scaling: Scaling) {
+        self.scaling = scaling;
+    }
+
+    pub fn gradient(&self) -> &Gradient {
+        &self.gradient
+    }
+
+    /// Returns every key with its press count and its count scaled to [0, 1] as used for the
+    /// gradient, sorted by key name.
+    ///
+    /// Like `colors`, counts are scaled only against the keys the heatmap lights. Media keys
+    /// pressed more often than all of them are at 1.
+    pub fn intensities(&self) -> Vec<(Key, u64, f64)> {
+        let values = self.data.iter().map(|(k, v)| (k, *v as f64)).collect();
+        let mut vec: Vec<_> = self.scale(values, 0.0).into_iter()
+            .map(|(k, v_scaled)| (k.clone(), self.data[k], v_scaled))
+            .collect();
+        vec.sort_by_key(|&(ref k, _, _)| format!("{:?}", k));
+        vec
+    }
+
+    /// Returns the colors of all keys the heatmap lights, scaled only against those keys.
+    pub fn colors<'a>(&'a self) -> Vec<KeyColor> {
+        let values = self.heat.iter().filter(|&(k, _)| is_lit(k)).map(|(k, v)| (k, *v)).collect();
+        self.scale(values, self.saturation).into_iter().map(|(k, v_scaled)| {
+            KeyColor::new(k.clone(), self.gradient.color_at(v_scaled))
+        }).collect()
+    }
+
+    /// Returns the colors of all keys the heatmap lights for arbitrary values instead of the
+    /// counts, using the heatmap's gradient and scaling. Keys without a value are colored as 0.
+    pub fn colors_for(&self, values: &HashMap<Key, f64>) -> Vec<KeyColor> {
+        let values = self.data.keys().filter(|k| is_lit(k))
+            .map(|k| (k, values.get(k).cloned().unwrap_or(0.0)))
+            .collect();
+        self.scale(values, 0.0).into_iter().map(|(k, v_scaled)| {
+            KeyColor::new(k.clone(), self.gradient.color_at(v_scaled))
+        }).collect()
+    }
+
+    /// Scales the values to [0, 1] against the values of the lit keys among them, so the
+    /// colors and exported intensities match.
+    fn scale<'a>(&self, values: Vec<(&'a Key, f64)>, saturation: f64) -> Vec<(&'a Key, f64)> {
+        let max = values.iter().filter(|&&(k, _)| is_lit(k)).map(|&(_, v)| v).fold(saturation, f64::max);
+        if max <= 0.0 {
+            return values.into_iter().map(|(k, _)| (k, 0.0)).collect();
+        }
+        match self.scaling {
+            Scaling::Linear => values.into_iter().map(|(k, v)| (k, (v / max).min(1.0))).collect(),
+            Scaling::Logarithmic => values.into_iter()
+                .map(|(k, v)| (k, ((1.0 + v).ln() / (1.0 + max).ln()).min(1.0)))
+                .collect(),
+            Scaling::Rank => {
+                let mut pressed: Vec<f64> = values.iter()
+                    .filter(|&&(k, v)| is_lit(k) && v > 0.0)
+                    .map(|&(_, v)| v)
+                    .collect();
+                pressed.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                let len = pressed.len() as f64;
+                values.into_iter().map(|(k, v)| {
+                    if v <= 0.0 || pressed.is_empty() {
+                        return (k, 0.0);
+                    }
+                    let rank = pressed.iter().take_while(|&&p| p <= v).count();
+                    (k, rank as f64 / len)
+                }).collect()
+            },
+        }
+    }
+}
+
+/// Whether the heatmap sets the color of the given key, which are all lightable keys but the
+/// logos.
+fn is_lit(key: &Key) -> bool {
+    match key {
+        // we don't want to set it for Logos
+        &Key::Logo(_) => false,
+        key => geometry::is_lightable(key),
+    }
+}
+
+fn secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::process;
+    use std::rc::Rc;
+    use std::time::Duration;
+    use g910::*;
+
+    use error::Error;
+    use gradient::Gradient;
+    use mock::Harness;
+    use super::{Heatmap, HeatmapHandler, Scaling};
+
+    #[test]
+    fn lights_pressed_keys() {
+        let mut harness = Harness::new(HeatmapHandler::new());
+        let (a, b) = (Key::Standard(StandardKey::A), Key::Standard(StandardKey::B));
+        harness.init().unwrap();
+        harness.click(a.clone()).unwrap();
+        harness.click(a.clone()).unwrap();
+        harness.click(b.clone()).unwrap();
+        let black = Some(Color::new(0, 0, 0));
+        let (hot, warm) = (harness.keyboard().color(&a), harness.keyboard().color(&b));
+        assert!(hot != black && warm != black && hot != warm);
+        assert_eq!(harness.keyboard().color(&Key::Standard(StandardKey::C)), black);
+    }
+
+    #[test]
+    fn saves_after_a_minute() {
+        let path = env::temp_dir().join(format!("heatmap-test-{}", process::id()));
+        let _ = fs::remove_file(&path);
+        {
+            let mut harness = Harness::new(HeatmapHandler::with_file(&path));
+            harness.init().unwrap();
+            harness.click(Key::Standard(StandardKey::A)).unwrap();
+            harness.advance(Duration::from_secs(59)).unwrap();
+            assert!(!path.exists());
+            harness.advance(Duration::from_secs(1)).unwrap();
+            assert!(path.exists());
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn loads_what_it_can() {
+        let path = env::temp_dir().join(format!("heatmap-load-test-{}", process::id()));
+        File::create(&path).unwrap().write_all(b"Standard(A) 5\n\
+            Standard(Nope) 7\n\
+            garbage\n\
+            Standard(B) many\n\
+            Gaming(G1)  3 \n\
+            \n").unwrap();
+        let heatmap = Heatmap::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let count = |key: Key| heatmap.data[&key];
+        assert_eq!(count(Key::Standard(StandardKey::A)), 5);
+        assert_eq!(count(Key::Gaming(GamingKey::G1)), 3);
+        // missing and malformed keys start at zero
+        assert_eq!(count(Key::Standard(StandardKey::B)), 0);
+        assert_eq!(count(Key::Standard(StandardKey::C)), 0);
+        assert_eq!(heatmap.data.len(), Key::values().len());
+    }
+
+    #[test]
+    fn reports_unreadable_file_and_keeps_it() {
+        // a directory can be opened, but not read
+        let path = env::temp_dir().join(format!("heatmap-dir-test-{}", process::id()));
+        fs::create_dir_all(&path).unwrap();
+        let errors = Rc::new(Cell::new(0));
+        let counter = errors.clone();
+        {
+            let mut harness = Harness::new(HeatmapHandler::with_file(&path)
+                .on_error(move |e| match e {
+                    Error::Io(_) => counter.set(counter.get() + 1),
+                    e => panic!("unexpected error {}", e),
+                }));
+            harness.init().unwrap();
+            assert_eq!(errors.get(), 1);
+            harness.click(Key::Standard(StandardKey::A)).unwrap();
+            harness.advance(Duration::from_secs(60)).unwrap();
+        }
+        assert_eq!(errors.get(), 1);
+        assert!(path.is_dir());
+        fs::remove_dir(&path).unwrap();
+    }
+
+    #[test]
+    fn decays_with_half_life() {
+        let mut harness = Harness::new(HeatmapHandler::new().decaying(Duration::from_secs(1)));
+        let a = Key::Standard(StandardKey::A);
+        harness.init().unwrap();
+        harness.tick().unwrap();
+        for _ in 0..8 {
+            harness.click(a.clone()).unwrap();
+        }
+        assert_eq!(harness.keyboard().color(&a), Some(Color::new(255, 0, 0)));
+        harness.advance(Duration::from_secs(1)).unwrap();
+        assert!((harness.handler().heatmap.heat[&a] - 4.0).abs() < 1e-9);
+        assert_eq!(harness.handler().heatmap.data[&a], 8);
+        assert_eq!(harness.keyboard().color(&a), Some(Gradient::heatmap().color_at(0.5)));
+        harness.advance(Duration::from_secs(20)).unwrap();
+        assert_eq!(harness.handler().heatmap.heat[&a], 0.0);
+        assert_eq!(harness.keyboard().color(&a), Some(Color::new(0, 0, 0)));
+    }
+
+    #[test]
+    fn counts_keys_without_lighting_media_keys_and_logos() {
+        let mut heatmap = Heatmap::new();
+        let keys = [Key::Media(MediaKey::Mute), Key::Gaming(GamingKey::G1), Key::Logo(LogoKey::G910)];
+        for key in &keys {
+            heatmap.increment(key);
+        }
+        for key in &keys {
+            assert_eq!(heatmap.intensities().into_iter().find(|&(ref k, _, _)| k == key).unwrap().1, 1);
+        }
+        let colors = heatmap.colors();
+        assert!(colors.iter().any(|kc| kc.key == keys[1] && kc.color != Color::new(0, 0, 0)));
+        assert!(!colors.iter().any(|kc| kc.key == keys[0] || kc.key == keys[2]));
+    }
+
+    fn intensity(heatmap: &Heatmap, key: &Key) -> f64 {
+        heatmap.intensities().into_iter().find(|&(ref k, _, _)| k == key).unwrap().2
+    }
+
+    #[test]
+    fn scales_skewed_counts() {
+        let (space, a, b) = (Key::Standard(StandardKey::Space), Key::Standard(StandardKey::A),
+            Key::Standard(StandardKey::B));
+        let mut heatmap = Heatmap::new();
+        for &(ref key, count) in &[(&space, 100), (&a, 10), (&b, 1)] {
+            for _ in 0..count {
+                heatmap.increment(key);
+            }
+        }
+        let c = Key::Standard(StandardKey::C);
+        let expected = [
+            (Scaling::Linear, 0.1, 0.01),
+            (Scaling::Logarithmic, 11f64.ln() / 101f64.ln(), 2f64.ln() / 101f64.ln()),
+            (Scaling::Rank, 2.0 / 3.0, 1.0 / 3.0),
+        ];
+        for &(scaling, ea, eb) in &expected {
+            heatmap.set_scaling(scaling);
+            assert_eq!(intensity(&heatmap, &space), 1.0, "{:?}", scaling);
+            assert!((intensity(&heatmap, &a) - ea).abs() < 1e-9, "{:?}", scaling);
+            assert!((intensity(&heatmap, &b) - eb).abs() < 1e-9, "{:?}", scaling);
+            assert_eq!(intensity(&heatmap, &c), 0.0, "{:?}", scaling);
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use g910::*;
+
+use super::secs;
+
+/// Pauses between two presses longer than this many milliseconds end a bigram / trigram and
+/// aren't counted as typing time.
+const SEQUENCE_TIMEOUT: u64 = 1000;
+/// Pauses between two characters longer than this many seconds aren't counted as typing time.
+const IDLE_TIMEOUT: u64 = 5;
+
+/// Finger pressing a key when touch typing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Finger {
+    LeftPinky,
+    LeftRing,
+    LeftMiddle,
+    LeftIndex,
+    Thumb,
+    RightIndex,
+    RightMiddle,
+    RightRing,
+    RightPinky,
+}
+
+impl Finger {
+    /// Returns the finger used for the given key when touch typing on the main block, or None
+    /// for keys outside of it.
+    pub fn for_key(key: &Key) -> Option<Finger> {
+        use g910::StandardKey as K;
+        let key = match key {
+            &Key::Standard(key) => key,
+            _ => return None,
+        };
+        Some(match key {
+            K::Circumflex | K::_1 | K::Tab | K::Q | K::CapsLock | K::A | K::LeftShift
+                | K::SmallerThan | K::Y | K::LeftControl | K::LeftWindows => Finger::LeftPinky,
+            K::_2 | K::W | K::S | K::X => Finger::LeftRing,
+            K::_3 | K::E | K::D | K::C => Finger::LeftMiddle,
+            K::_4 | K::_5 | K::R | K::T | K::F | K::G | K::V | K::B => Finger::LeftIndex,
+            K::LeftAlt | K::Space | K::RightAlt => Finger::Thumb,
+            K::_6 | K::_7 | K::Z | K::U | K::H | K::J | K::N | K::M => Finger::RightIndex,
+            K::_8 | K::I | K::K | K::Comma => Finger::RightMiddle,
+            K::_9 | K::O | K::L | K::Dot => Finger::RightRing,
+            K::_0 | K::Sz | K::Tick | K::Backspace | K::P | K::Uuml | K::Plus | K::Return
+                | K::Ouml | K::Auml | K::Sharp | K::Pipe | K::Minus | K::RightShift
+                | K::RightWindows | K::Menu | K::RightControl => Finger::RightPinky,
+            _ => return None,
+        })
+    }
+}
+
+/// Whether the given key produces a character, used for calculating the typing speed.
+fn is_character(key: &Key) -> bool {
+    use g910::StandardKey as K;
+    match key {
+        &Key::Standard(key) => match key {
+            K::A | K::B | K::C | K::D | K::E | K::F | K::G | K::H | K::I | K::J | K::K | K::L
+                | K::M | K::N | K::O | K::P | K::Q | K::R | K::S | K::T | K::U | K::V | K::W
+                | K::X | K::Y | K::Z | K::_1 | K::_2 | K::_3 | K::_4 | K::_5 | K::_6 | K::_7
+                | K::_8 | K::_9 | K::_0 | K::Space | K::Sz | K::Tick | K::Uuml | K::Plus | K::Pipe
+                | K::Sharp | K::Ouml | K::Auml | K::Circumflex | K::Comma | K::Dot | K::Minus
+                | K::SmallerThan | K::Return | K::Tab => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Typing statistics of a session, recorded from key presses and releases.
+pub struct Stats {
+    start: Option<Instant>,
+    presses: HashMap<Key, u64>,
+    held: HashMap<Key, Instant>,
+    holds: HashMap<Key, (Duration, u32)>,
+    bigrams: HashMap<(Key, Key), u64>,
+    trigrams: HashMap<(Key, Key, Key), u64>,
+    recent: VecDeque<Key>,
+    last_press: Option<Instant>,
+    characters: u64,
+    last_character: Option<Instant>,
+    typing_time: Duration,
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats {
+            start: None,
+            presses: HashMap::new(),
+            held: HashMap::new(),
+            holds: HashMap::new(),
+            bigrams: HashMap::new(),
+            trigrams: HashMap::new(),
+            recent: VecDeque::new(),
+            last_press: None,
+            characters: 0,
+            last_character: None,
+            typing_time: Duration::from_secs(0),
+        }
+    }
+
+    pub fn press(&mut self, key: &Key, now: Instant) {
+        if self.start.is_none() {
+            self.start = Some(now);
+        }
+        *self.presses.entry(key.clone()).or_insert(0) += 1;
+        self.held.insert(key.clone(), now);
+
+        let timeout = Duration::from_millis(SEQUENCE_TIMEOUT);
+        match self.last_press {
+            Some(last) if now.duration_since(last) <= timeout => {},
+            _ => self.recent.clear(),
+        }
+        self.last_press = Some(now);
+        self.recent.push_back(key.clone());
+        if self.recent.len() > 3 {
+            self.recent.pop_front();
+        }
+        let len = self.recent.len();
+        if len >= 2 {
+            let bigram = (self.recent[len-2].clone(), self.recent[len-1].clone());
+            *self.bigrams.entry(bigram).or_insert(0) += 1;
+        }
+        if len == 3 {
+            let trigram = (self.recent[0].clone(), self.recent[1].clone(), self.recent[2].clone());
+            *self.trigrams.entry(trigram).or_insert(0) += 1;
+        }
+
+        if is_character(key) {
+            self.characters += 1;
+            if let Some(last) = self.last_character {
+                let pause = now.duration_since(last);
+                if pause <= Duration::from_secs(IDLE_TIMEOUT) {
+                    self.typing_time += pause;
+                }
+            }
+            self.last_character = Some(now);
+        }
+    }
+
+    pub fn release(&mut self, key: &Key, now: Instant) {
+        if let Some(pressed) = self.held.remove(key) {
+            let hold = self.holds.entry(key.clone()).or_insert((Duration::from_secs(0), 0));
+            hold.0 += now.duration_since(pressed);
+            hold.1 += 1;
+        }
+    }
+
+    /// Returns when the first key of the session was pressed.
+    pub fn start(&self) -> Option<Instant> {
+        self.start
+    }
+
+    /// Returns the typing speed in words of five characters per minute of typing.
+    ///
+    /// Pauses of more than five seconds between two characters don't count as typing time.
+    pub fn words_per_minute(&self) -> f64 {
+        let minutes = secs(self.typing_time) / 60.0;
+        if minutes <= 0.0 {
+            return 0.0;
+        }
+        self.characters as f64 / 5.0 / minutes
+    }
+
+    /// Returns how often each key has been pressed during the session.
+    pub fn presses(&self) -> &HashMap<Key, u64> {
+        &self.presses
+    }
+
+    /// Returns the bigrams typed during the session, most common first.
+    ///
+    /// Two presses only form a bigram if there is less than a second between them.
+    pub fn bigrams(&self) -> Vec<((Key, Key), u64)> {
+        let mut vec: Vec<_> = self.bigrams.iter().map(|(k, v)| (k.clone(), *v)).collect();
+        vec.sort_by(|a, b| b.1.cmp(&a.1));
+        vec
+    }
+
+    /// Returns the trigrams typed during the session, most common first.
+    pub fn trigrams(&self) -> Vec<((Key, Key, Key), u64)> {
+        let mut vec: Vec<_> = self.trigrams.iter().map(|(k, v)| (k.clone(), *v)).collect();
+        vec.sort_by(|a, b| b.1.cmp(&a.1));
+        vec
+    }
+
+    /// Returns how many bigrams start with each key.
+    pub fn bigram_starts(&self) -> HashMap<Key, u64> {
+        let mut starts = HashMap::new();
+        for (&(ref first, _), count) in &self.bigrams {
+            *starts.entry(first.clone()).or_insert(0) += *count;
+        }
+        starts
+    }
+
+    /// Returns how long the given key has been held on average.
+    pub fn average_hold(&self, key: &Key) -> Option<Duration> {
+        self.holds.get(key).map(|&(total, count)| total / count)
+    }
+
+    /// Returns how long each released key has been held on average.
+    pub fn average_holds(&self) -> HashMap<Key, Duration> {
+        self.holds.iter().map(|(k, &(total, count))| (k.clone(), total / count)).collect()
+    }
+
+    /// Returns how many presses each finger made, for keys of the main block.
+    pub fn finger_load(&self) -> HashMap<Finger, u64> {
+        let mut load = HashMap::new();
+        for (key, count) in &self.presses {
+            if let Some(finger) = Finger::for_key(key) {
+                *load.entry(finger).or_insert(0) += *count;
+            }
+        }
+        load
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+    use g910::*;
+
+    use super::{Finger, Stats};
+
+    fn key(key: StandardKey) -> Key {
+        Key::Standard(key)
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn counts_words_per_minute_of_typing_time() {
+        let mut stats = Stats::new();
+        let start = Instant::now();
+        for i in 0..10 {
+            stats.press(&key(StandardKey::A), start + ms(i * 100));
+        }
+        // 10 characters in 0.9 seconds are 2 words in 0.015 minutes
+        assert!((stats.words_per_minute() - 2.0 / 0.015).abs() < 1e-6);
+        // pauses longer than five seconds don't count
+        stats.press(&key(StandardKey::A), start + ms(900 + 6000));
+        assert!((stats.words_per_minute() - 2.2 / 0.015).abs() < 1e-6);
+        // neither do keys not producing a character
+        stats.press(&key(StandardKey::Esc), start + ms(7000));
+        assert!((stats.words_per_minute() - 2.2 / 0.015).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ends_sequences_at_pauses() {
+        let mut stats = Stats::new();
+        let start = Instant::now();
+        let (a, b, c, d) = (key(StandardKey::A), key(StandardKey::B), key(StandardKey::C),
+            key(StandardKey::D));
+        stats.press(&a, start);
+        stats.press(&b, start + ms(1000));
+        stats.press(&c, start + ms(2001));
+        stats.press(&d, start + ms(2100));
+        stats.press(&a, start + ms(2200));
+        let bigrams: HashMap<_, _> = stats.bigrams().into_iter().collect();
+        assert_eq!(bigrams.len(), 3);
+        assert_eq!(bigrams.get(&(a.clone(), b.clone())), Some(&1));
+        assert_eq!(bigrams.get(&(c.clone(), d.clone())), Some(&1));
+        assert_eq!(bigrams.get(&(d.clone(), a.clone())), Some(&1));
+        assert_eq!(stats.trigrams(), vec![((c.clone(), d.clone(), a.clone()), 1)]);
+        assert_eq!(stats.bigram_starts().get(&a), Some(&1));
+        assert_eq!(stats.bigram_starts().get(&b), None);
+    }
+
+    #[test]
+    fn averages_hold_times() {
+        let mut stats = Stats::new();
+        let start = Instant::now();
+        let a = key(StandardKey::A);
+        stats.press(&a, start);
+        stats.release(&a, start + ms(100));
+        stats.press(&a, start + ms(1000));
+        assert_eq!(stats.average_hold(&a), Some(ms(100)));
+        stats.release(&a, start + ms(1300));
+        assert_eq!(stats.average_hold(&a), Some(ms(200)));
+        assert_eq!(stats.average_hold(&key(StandardKey::B)), None);
+        // releases without a press aren't counted
+        stats.release(&key(StandardKey::B), start + ms(1400));
+        assert_eq!(stats.average_holds().len(), 1);
+    }
+
+    #[test]
+    fn counts_presses_per_finger() {
+        let mut stats = Stats::new();
+        let start = Instant::now();
+        for &k in &[StandardKey::A, StandardKey::Q, StandardKey::S, StandardKey::Space,
+                StandardKey::Esc] {
+            stats.press(&key(k), start);
+        }
+        let load = stats.finger_load();
+        assert_eq!(load.get(&Finger::LeftPinky), Some(&2));
+        assert_eq!(load.get(&Finger::LeftRing), Some(&1));
+        assert_eq!(load.get(&Finger::Thumb), Some(&1));
+        assert_eq!(load.values().sum::<u64>(), 4);
+    }
+}
@@ -0,0 +1,290 @@
+use g910::StandardKey;
+use g910::StandardKey as K;
+use uinput::event::{Keyboard as UinputKey};
+use uinput::event::keyboard::{Key as UinputStandardKey, Misc, KeyPad};
+
+/// Physical layout and legends of a G910.
+///
+/// The G910 reports keys by their position, named after the legends of the German board, and
+/// evdev keycodes describe positions as well. Therefore all layouts share one positional mapping
+/// (the one of `DeQwertz`), except for keys which only exist on ISO or on ANSI boards. Which
+/// character a key finally produces is decided by the keymap of the desktop, e.g. `setxkbmap us`.
+///
+/// Additionally a layout knows the legends printed on its keys, so keys can be referred to by
+/// what they read on the actual board, e.g. in remap configurations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// German ISO board, the layout the key names of the G910 are based on.
+    DeQwertz,
+    /// US ANSI board, without the key between left shift and Y and with a wide left shift.
+    UsAnsi,
+    /// UK ISO board.
+    UkIso,
+    /// French ISO board.
+    FrAzerty,
+}
+
+impl Layout {
+    /// Returns the layout for one of the names `de`, `us`, `uk` or `fr`.
+    pub fn from_name(name: &str) -> Option<Layout> {
+        match &*name.to_lowercase() {
+            "de" | "de-qwertz" => Some(Layout::DeQwertz),
+            "us" | "us-ansi" => Some(Layout::UsAnsi),
+            "uk" | "gb" | "uk-iso" => Some(Layout::UkIso),
+            "fr" | "fr-azerty" => Some(Layout::FrAzerty),
+            _ => None,
+        }
+    }
+
+    /// Returns the evdev keycode to emit for the given key.
+    pub fn to_uinput_key(&self, key: StandardKey) -> Option<UinputKey> {
+        match (*self, key) {
+            // ANSI boards don't have these keys, their backslash key is reported as Pipe
+            (Layout::UsAnsi, K::SmallerThan) | (Layout::UsAnsi, K::Sharp) => None,
+            (_, key) => positional(&key),
+        }
+    }
+
+    /// Returns the legend printed on the given key on a board of this layout.
+    ///
+    /// For keys whose legend is the same on all layouts, like the letters B or the function
+    /// keys, the name of the key is returned.
+    pub fn legend(&self, key: StandardKey) -> String {
+        self.legends().iter()
+            .find(|&&(k, _)| k == key)
+            .map(|&(_, legend)| legend.to_string())
+            .unwrap_or_else(|| format!("{:?}", key))
+    }
+
+    /// Returns the key with the given legend on a board of this layout, or with the given name
+    /// as reported by the G910.
+    pub fn key_for_legend(&self, legend: &str) -> Option<StandardKey> {
+        if let Some(&(key, _)) = self.legends().iter().find(|&&(_, l)| l == legend) {
+            return Some(key);
+        }
+        // don't let the name of a key relabelled on this layout shadow the legend
+        STANDARD_KEYS.iter().cloned()
+            .find(|key| format!("{:?}", key) == legend)
+    }
+
+    fn legends(&self) -> &'static [(StandardKey, &'static str)] {
+        match *self {
+            Layout::DeQwertz => DE_QWERTZ,
+            Layout::UsAnsi => US_ANSI,
+            Layout::UkIso => UK_ISO,
+            Layout::FrAzerty => FR_AZERTY,
+        }
+    }
+}
+
+/// All standard keys, for looking them up by name.
+pub const STANDARD_KEYS: &'static [StandardKey] = &[
+    K::A, K::B, K::C, K::D, K::E, K::F, K::G, K::H, K::I, K::J, K::K, K::L, K::M, K::N, K::O, K::P,
+    K::Q, K::R, K::S, K::T, K::U, K::V, K::W, K::X, K::Z, K::Y, K::_1, K::_2, K::_3, K::_4, K::_5,
+    K::_6, K::_7, K::_8, K::_9, K::_0, K::Return, K::Esc, K::Backspace, K::Tab, K::Space, K::Sz,
+    K::Tick, K::Uuml, K::Plus, K::Pipe, K::Sharp, K::Ouml, K::Auml, K::Circumflex, K::Comma, K::Dot,
+    K::Minus, K::CapsLock, K::F1, K::F2, K::F3, K::F4, K::F5, K::F6, K::F7, K::F8, K::F9, K::F10,
+    K::F11, K::F12, K::Print, K::ScrollLock, K::Pause, K::Insert, K::Home, K::PageUp, K::Delete,
+    K::End, K::PageDown, K::Right, K::Left, K::Down, K::Up, K::NumLock, K::NumSlash, K::NumStar,
+    K::NumMinus, K::NumPlus, K::NumReturn, K::Num1, K::Num2, K::Num3, K::Num4, K::Num5, K::Num6,
+    K::Num7, K::Num8, K::Num9, K::Num0, K::NumComma, K::SmallerThan, K::Menu, K::International1,
+    K::International2, K::International3, K::International4, K::International5, K::LeftControl,
+    K::LeftShift, K::LeftAlt, K::LeftWindows, K::RightControl, K::RightShift, K::RightAlt,
+    K::RightWindows,
+];
+
+const DE_QWERTZ: &'static [(StandardKey, &'static str)] = &[
+    (K::Circumflex, "^"), (K::Sz, "ß"), (K::Tick, "´"), (K::Uuml, "Ü"), (K::Plus, "+"),
+    (K::Ouml, "Ö"), (K::Auml, "Ä"), (K::Sharp, "#"), (K::SmallerThan, "<"), (K::Comma, ","),
+    (K::Dot, "."), (K::Minus, "-"),
+];
+
+const US_ANSI: &'static [(StandardKey, &'static str)] = &[
+    (K::Circumflex, "`"), (K::Sz, "-"), (K::Tick, "="), (K::Z, "Y"), (K::Uuml, "["), (K::Plus, "]"),
+    (K::Pipe, "\\"), (K::Ouml, ";"), (K::Auml, "'"), (K::Y, "Z"), (K::Comma, ","), (K::Dot, "."),
+    (K::Minus, "/"),
+];
+
+const UK_ISO: &'static [(StandardKey, &'static str)] = &[
+    (K::Circumflex, "`"), (K::Sz, "-"), (K::Tick, "="), (K::Z, "Y"), (K::Uuml, "["), (K::Plus, "]"),
+    (K::Ouml, ";"), (K::Auml, "'"), (K::Sharp, "#"), (K::SmallerThan, "\\"), (K::Y, "Z"),
+    (K::Comma, ","), (K::Dot, "."), (K::Minus, "/"),
+];
+
+const FR_AZERTY: &'static [(StandardKey, &'static str)] = &[
+    (K::Circumflex, "²"), (K::_1, "&"), (K::_2, "é"), (K::_3, "\""), (K::_4, "'"), (K::_5, "("),
+    (K::_6, "-"), (K::_7, "è"), (K::_8, "_"), (K::_9, "ç"), (K::_0, "à"), (K::Sz, ")"), (K::Tick, "="),
+    (K::Q, "A"), (K::W, "Z"), (K::Z, "Y"), (K::Uuml, "^"), (K::Plus, "$"),
+    (K::A, "Q"), (K::Ouml, "M"), (K::Auml, "ù"), (K::Sharp, "*"),
+    (K::SmallerThan, "<"), (K::Y, "W"), (K::M, ","), (K::Comma, ";"), (K::Dot, ":"), (K::Minus, "!"),
+];
+
+/// Maps keys to the evdev keycode at the same physical position.
+fn positional(key: &StandardKey) -> Option<UinputKey> {
+    match key {
+        &StandardKey::None => None,
+        &StandardKey::A => Some(UinputKey::Key(UinputStandardKey::A)),
+        &StandardKey::B => Some(UinputKey::Key(UinputStandardKey::B)),
+        &StandardKey::C => Some(UinputKey::Key(UinputStandardKey::C)),
+        &StandardKey::D => Some(UinputKey::Key(UinputStandardKey::D)),
+        &StandardKey::E => Some(UinputKey::Key(UinputStandardKey::E)),
+        &StandardKey::F => Some(UinputKey::Key(UinputStandardKey::F)),
+        &StandardKey::G => Some(UinputKey::Key(UinputStandardKey::G)),
+        &StandardKey::H => Some(UinputKey::Key(UinputStandardKey::H)),
+        &StandardKey::I => Some(UinputKey::Key(UinputStandardKey::I)),
+        &StandardKey::J => Some(UinputKey::Key(UinputStandardKey::J)),
+        &StandardKey::K => Some(UinputKey::Key(UinputStandardKey::K)),
+        &StandardKey::L => Some(UinputKey::Key(UinputStandardKey::L)),
+        &StandardKey::M => Some(UinputKey::Key(UinputStandardKey::M)),
+        &StandardKey::N => Some(UinputKey::Key(UinputStandardKey::N)),
+        &StandardKey::O => Some(UinputKey::Key(UinputStandardKey::O)),
+        &StandardKey::P => Some(UinputKey::Key(UinputStandardKey::P)),
+        &StandardKey::Q => Some(UinputKey::Key(UinputStandardKey::Q)),
+        &StandardKey::R => Some(UinputKey::Key(UinputStandardKey::R)),
+        &StandardKey::S => Some(UinputKey::Key(UinputStandardKey::S)),
+        &StandardKey::T => Some(UinputKey::Key(UinputStandardKey::T)),
+        &StandardKey::U => Some(UinputKey::Key(UinputStandardKey::U)),
+        &StandardKey::V => Some(UinputKey::Key(UinputStandardKey::V)),
+        &StandardKey::W => Some(UinputKey::Key(UinputStandardKey::W)),
+        &StandardKey::X => Some(UinputKey::Key(UinputStandardKey::X)),
+        &StandardKey::Z => Some(UinputKey::Key(UinputStandardKey::Y)),
+        &StandardKey::Y => Some(UinputKey::Key(UinputStandardKey::Z)),
+        &StandardKey::_1 => Some(UinputKey::Key(UinputStandardKey::_1)),
+        &StandardKey::_2 => Some(UinputKey::Key(UinputStandardKey::_2)),
+        &StandardKey::_3 => Some(UinputKey::Key(UinputStandardKey::_3)),
+        &StandardKey::_4 => Some(UinputKey::Key(UinputStandardKey::_4)),
+        &StandardKey::_5 => Some(UinputKey::Key(UinputStandardKey::_5)),
+        &StandardKey::_6 => Some(UinputKey::Key(UinputStandardKey::_6)),
+        &StandardKey::_7 => Some(UinputKey::Key(UinputStandardKey::_7)),
+        &StandardKey::_8 => Some(UinputKey::Key(UinputStandardKey::_8)),
+        &StandardKey::_9 => Some(UinputKey::Key(UinputStandardKey::_9)),
+        &StandardKey::_0 => Some(UinputKey::Key(UinputStandardKey::_0)),
+        &StandardKey::Return => Some(UinputKey::Key(UinputStandardKey::Enter)),
+        &StandardKey::Esc => Some(UinputKey::Key(UinputStandardKey::Esc)),
+        &StandardKey::Backspace => Some(UinputKey::Key(UinputStandardKey::Backspace)),
+        &StandardKey::Tab => Some(UinputKey::Key(UinputStandardKey::Tab)),
+        &StandardKey::Space => Some(UinputKey::Key(UinputStandardKey::Space)),
+        &StandardKey::Sz => Some(UinputKey::Key(UinputStandardKey::Minus)),
+        &StandardKey::Tick => Some(UinputKey::Key(UinputStandardKey::Equal)),
+        &StandardKey::Uuml => Some(UinputKey::Key(UinputStandardKey::LeftBrace)),
+        &StandardKey::Plus => Some(UinputKey::Key(UinputStandardKey::RightBrace)),
+        &StandardKey::Pipe => Some(UinputKey::Key(UinputStandardKey::BackSlash)),
+        &StandardKey::Sharp => Some(UinputKey::Key(UinputStandardKey::BackSlash)),
+        &StandardKey::Ouml => Some(UinputKey::Key(UinputStandardKey::SemiColon)),
+        &StandardKey::Auml => Some(UinputKey::Key(UinputStandardKey::Apostrophe)),
+        &StandardKey::Circumflex => Some(UinputKey::Key(UinputStandardKey::Grave)),
+        &StandardKey::Comma => Some(UinputKey::Key(UinputStandardKey::Comma)),
+        &StandardKey::Dot => Some(UinputKey::Key(UinputStandardKey::Dot)),
+        &StandardKey::Minus => Some(UinputKey::Key(UinputStandardKey::Slash)),
+        &StandardKey::CapsLock => Some(UinputKey::Key(UinputStandardKey::CapsLock)),
+        &StandardKey::F1 => Some(UinputKey::Key(UinputStandardKey::F1)),
+        &StandardKey::F2 => Some(UinputKey::Key(UinputStandardKey::F2)),
+        &StandardKey::F3 => Some(UinputKey::Key(UinputStandardKey::F3)),
+        &StandardKey::F4 => Some(UinputKey::Key(UinputStandardKey::F4)),
+        &StandardKey::F5 => Some(UinputKey::Key(UinputStandardKey::F5)),
+        &StandardKey::F6 => Some(UinputKey::Key(UinputStandardKey::F6)),
+        &StandardKey::F7 => Some(UinputKey::Key(UinputStandardKey::F7)),
+        &StandardKey::F8 => Some(UinputKey::Key(UinputStandardKey::F8)),
+        &StandardKey::F9 => Some(UinputKey::Key(UinputStandardKey::F9)),
+        &StandardKey::F10 => Some(UinputKey::Key(UinputStandardKey::F10)),
+        &StandardKey::F11 => Some(UinputKey::Key(UinputStandardKey::F11)),
+        &StandardKey::F12 => Some(UinputKey::Key(UinputStandardKey::F12)),
+        &StandardKey::Print => Some(UinputKey::Key(UinputStandardKey::SysRq)),
+        &StandardKey::ScrollLock => Some(UinputKey::Key(UinputStandardKey::ScrollLock)),
+        &StandardKey::Pause => Some(UinputKey::Misc(Misc::Pause)),
+        &StandardKey::Insert => Some(UinputKey::Key(UinputStandardKey::Insert)),
+        &StandardKey::Home => Some(UinputKey::Key(UinputStandardKey::Home)),
+        &StandardKey::PageUp => Some(UinputKey::Key(UinputStandardKey::PageUp)),
+        &StandardKey::Delete => Some(UinputKey::Key(UinputStandardKey::Delete)),
+        &StandardKey::End => Some(UinputKey::Key(UinputStandardKey::End)),
+        &StandardKey::PageDown => Some(UinputKey::Key(UinputStandardKey::PageDown)),
+        &StandardKey::Right => Some(UinputKey::Key(UinputStandardKey::Right)),
+        &StandardKey::Left => Some(UinputKey::Key(UinputStandardKey::Left)),
+        &StandardKey::Down => Some(UinputKey::Key(UinputStandardKey::Down)),
+        &StandardKey::Up => Some(UinputKey::Key(UinputStandardKey::Up)),
+        &StandardKey::NumLock => Some(UinputKey::Key(UinputStandardKey::NumLock)),
+        &StandardKey::NumSlash => Some(UinputKey::KeyPad(KeyPad::Slash)),
+        &StandardKey::NumStar => Some(UinputKey::KeyPad(KeyPad::Asterisk)),
+        &StandardKey::NumMinus => Some(UinputKey::KeyPad(KeyPad::Minus)),
+        &StandardKey::NumPlus => Some(UinputKey::KeyPad(KeyPad::Plus)),
+        &StandardKey::NumReturn => Some(UinputKey::KeyPad(KeyPad::Enter)),
+        &StandardKey::Num1 => Some(UinputKey::KeyPad(KeyPad::_1)),
+        &StandardKey::Num2 => Some(UinputKey::KeyPad(KeyPad::_2)),
+        &StandardKey::Num3 => Some(UinputKey::KeyPad(KeyPad::_3)),
+        &StandardKey::Num4 => Some(UinputKey::KeyPad(KeyPad::_4)),
+        &StandardKey::Num5 => Some(UinputKey::KeyPad(KeyPad::_5)),
+        &StandardKey::Num6 => Some(UinputKey::KeyPad(KeyPad::_6)),
+        &StandardKey::Num7 => Some(UinputKey::KeyPad(KeyPad::_7)),
+        &StandardKey::Num8 => Some(UinputKey::KeyPad(KeyPad::_8)),
+        &StandardKey::Num9 => Some(UinputKey::KeyPad(KeyPad::_9)),
+        &StandardKey::Num0 => Some(UinputKey::KeyPad(KeyPad::_0)),
+        &StandardKey::NumComma => Some(UinputKey::KeyPad(KeyPad::Dot)),
+        &StandardKey::SmallerThan => Some(UinputKey::Misc(Misc::ND102)),
+        &StandardKey::Menu => Some(UinputKey::Misc(Misc::Menu)),
+        &StandardKey::International1 => None,
+        &StandardKey::International2 => None,
+        &StandardKey::International3 => None,
+        &StandardKey::International4 => None,
+        &StandardKey::International5 => None,
+        &StandardKey::LeftControl => Some(UinputKey::Key(UinputStandardKey::LeftControl)),
+        &StandardKey::LeftShift => Some(UinputKey::Key(UinputStandardKey::LeftShift)),
+        &StandardKey::LeftAlt => Some(UinputKey::Key(UinputStandardKey::LeftAlt)),
+        &StandardKey::LeftWindows => Some(UinputKey::Key(UinputStandardKey::LeftMeta)),
+        &StandardKey::RightControl => Some(UinputKey::Key(UinputStandardKey::RightControl)),
+        &StandardKey::RightShift => Some(UinputKey::Key(UinputStandardKey::RightShift)),
+        &StandardKey::RightAlt => Some(UinputKey::Key(UinputStandardKey::RightAlt)),
+        &StandardKey::RightWindows => Some(UinputKey::Key(UinputStandardKey::RightMeta))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use g910::StandardKey;
+    use uinput::event::{Keyboard as UinputKey};
+    use uinput::event::keyboard::{Key as UinputStandardKey, Misc};
+
+    use super::{Layout, STANDARD_KEYS};
+
+    const LAYOUTS: &'static [Layout] = &[Layout::DeQwertz, Layout::UsAnsi, Layout::UkIso, Layout::FrAzerty];
+
+    #[test]
+    fn maps_keys_by_position() {
+        let de = Layout::DeQwertz;
+        assert_eq!(de.to_uinput_key(StandardKey::Z), Some(UinputKey::Key(UinputStandardKey::Y)));
+        assert_eq!(de.to_uinput_key(StandardKey::Y), Some(UinputKey::Key(UinputStandardKey::Z)));
+        assert_eq!(de.to_uinput_key(StandardKey::Sharp), Some(UinputKey::Key(UinputStandardKey::BackSlash)));
+        assert_eq!(de.to_uinput_key(StandardKey::SmallerThan), Some(UinputKey::Misc(Misc::ND102)));
+        assert_eq!(de.to_uinput_key(StandardKey::None), None);
+        // all layouts share the German positions, except for the keys missing on ANSI boards
+        for layout in LAYOUTS {
+            for &key in STANDARD_KEYS {
+                let expected = match (*layout, key) {
+                    (Layout::UsAnsi, StandardKey::SmallerThan) | (Layout::UsAnsi, StandardKey::Sharp) => None,
+                    _ => de.to_uinput_key(key),
+                };
+                assert_eq!(layout.to_uinput_key(key), expected, "{:?} on {:?}", key, layout);
+            }
+        }
+        assert_eq!(Layout::UsAnsi.to_uinput_key(StandardKey::Pipe),
+            Some(UinputKey::Key(UinputStandardKey::BackSlash)));
+    }
+
+    #[test]
+    fn finds_keys_by_legend() {
+        assert_eq!(Layout::UsAnsi.key_for_legend("Z"), Some(StandardKey::Y));
+        assert_eq!(Layout::UsAnsi.key_for_legend("Y"), Some(StandardKey::Z));
+        assert_eq!(Layout::DeQwertz.key_for_legend("Z"), Some(StandardKey::Z));
+        assert_eq!(Layout::UkIso.key_for_legend("#"), Some(StandardKey::Sharp));
+        assert_eq!(Layout::UkIso.key_for_legend("\\"), Some(StandardKey::SmallerThan));
+        assert_eq!(Layout::UsAnsi.key_for_legend("\\"), Some(StandardKey::Pipe));
+        assert_eq!(Layout::FrAzerty.key_for_legend("A"), Some(StandardKey::Q));
+        // keys are found by their name as well
+        assert_eq!(Layout::UsAnsi.key_for_legend("Backspace"), Some(StandardKey::Backspace));
+        assert_eq!(Layout::UsAnsi.key_for_legend("Nope"), None);
+        for layout in LAYOUTS {
+            for &key in STANDARD_KEYS {
+                assert_eq!(layout.key_for_legend(&layout.legend(key)), Some(key), "{:?} on {:?}", key, layout);
+            }
+        }
+    }
+}
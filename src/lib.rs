@@ -4,14 +4,40 @@ extern crate libusb;
 extern crate g910;
 extern crate uinput;
 extern crate rand;
+extern crate toml;
+extern crate libc;
 
+pub use ambient::{Breathing, ColorCycle, Direction, RainbowWave, Starlight};
+pub use fade::FadeHandler;
 pub use flash::FlashHandler;
-pub use heatmap::HeatmapHandler;
-pub use u_input::UinputHandler;
+pub use heatmap::{Heatmap, HeatmapHandler, Scaling, View, Stats, Finger};
+pub use backend::{KeyboardHandler, Leds};
+pub use gradient::{Gradient, Interpolation};
+pub use geometry::{Geometry, KeyGeometry, Variant};
+pub use layout::Layout;
+pub use config::{Config, DualRole, Hold, Layer, Repeat};
+pub use u_input::{UinputHandler, UinputBuilder, KeySink, Lock, LockLeds, Macros, MacroEvent, VirtualDevice};
+pub use error::{Error, Result};
 pub use snake::Snake;
+pub use profile::{Profile, ProfileManager};
+pub use chord::{Action, ChordHandler};
+pub use ripple::RippleHandler;
 
+pub mod backend;
+pub mod config;
+pub mod geometry;
+pub mod gradient;
+pub mod layout;
+pub mod mock;
+pub mod snapshot;
+mod ambient;
+mod chord;
+mod error;
+mod fade;
 mod flash;
 mod heatmap;
+mod profile;
+mod ripple;
 mod u_input;
 mod snake;
 
@@ -0,0 +1,346 @@
+//! Stand-ins for the keyboard and the uinput device, to run handlers without any hardware.
+//!
+//! A `Harness` drives a `KeyboardHandler` through init, key events and simulated time, while a
+//! `MockKeyboard` records the resulting colors into a virtual frame:
+//!
+//! ```ignore
+//! let mut harness = Harness::new(FlashHandler::new());
+//! harness.init().unwrap();
+//! harness.press(Key::Standard(StandardKey::A)).unwrap();
+//! assert_eq!(harness.keyboard().color(&Key::Standard(StandardKey::A)), Some(Color::new(255, 0, 0)));
+//! ```
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use libusb::Result as UsbResult;
+use uinput::event::{Keyboard as UinputKey};
+use g910::*;
+
+use backend::{KeyboardHandler, Leds};
+use error;
+use u_input::{KeySink, Lock};
+
+/// A call made to a `MockKeyboard`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Call {
+    SetAllColors(Color),
+    SetKeyColors(Vec<(Key, Color)>),
+}
+
+/// Keyboard recording all colors set on it.
+pub struct MockKeyboard {
+    frame: HashMap<Key, Color>,
+    calls: Vec<Call>,
+}
+
+impl MockKeyboard {
+    /// Creates a keyboard with all keys turned off.
+    pub fn new() -> MockKeyboard {
+        MockKeyboard {
+            frame: Key::values().into_iter().map(|k| (k, Color::new(0, 0, 0))).collect(),
+            calls: Vec::new(),
+        }
+    }
+
+    /// Returns the current color of every key.
+    pub fn frame(&self) -> &HashMap<Key, Color> {
+        &self.frame
+    }
+
+    pub fn color(&self, key: &Key) -> Option<Color> {
+        self.frame.get(key).cloned()
+    }
+
+    /// Returns all calls made since creation or the last `clear_calls`.
+    pub fn calls(&self) -> &[Call] {
+        &self.calls
+    }
+
+    pub fn clear_calls(&mut self) {
+        self.calls.clear();
+    }
+}
+
+impl Leds for MockKeyboard {
+    fn set_all_colors(&mut self, color: Color) -> UsbResult<()> {
+        for c in self.frame.values_mut() {
+            *c = color;
+        }
+        self.calls.push(Call::SetAllColors(color));
+        Ok(())
+    }
+
+    fn set_key_colors(&mut self, key_colors: Vec<KeyColor>) -> UsbResult<()> {
+        let key_colors: Vec<_> = key_colors.into_iter().map(|kc| (kc.key, kc.color)).collect();
+        for &(ref key, color) in &key_colors {
+            self.frame.insert(key.clone(), color);
+        }
+        self.calls.push(Call::SetKeyColors(key_colors));
+        Ok(())
+    }
+
+    fn color(&self, key: &Key) -> Option<Color> {
+        MockKeyboard::color(self, key)
+    }
+}
+
+/// An event received by a `MockSink`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SinkEvent {
+    Press(UinputKey),
+    Release(UinputKey),
+    Repeat(UinputKey),
+    Synchronize,
+}
+
+/// Uinput device stand-in recording all events.
+///
+/// Clones share their events and LEDs, so a clone can be inspected and have its LEDs set after
+/// the sink has been moved into a `UinputHandler`.
+#[derive(Clone)]
+pub struct MockSink {
+    events: Arc<Mutex<Vec<SinkEvent>>>,
+    leds: Arc<Mutex<Vec<(Lock, bool)>>>,
+    failing: Arc<Mutex<bool>>,
+}
+
+impl MockSink {
+    pub fn new() -> MockSink {
+        MockSink {
+            events: Arc::new(Mutex::new(Vec::new())),
+            leds: Arc::new(Mutex::new(Vec::new())),
+            failing: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    /// Lets presses fail with an io error like an unplugged device, while releases keep
+    /// succeeding so releasing held keys can be observed.
+    pub fn fail_presses(&self, failing: bool) {
+        *self.failing.lock().unwrap() = failing;
+    }
+
+    /// Turns the lock's LED on or off like the desktop would, returned by the next `leds`.
+    pub fn set_led(&self, lock: Lock, on: bool) {
+        self.leds.lock().unwrap().push((lock, on));
+    }
+
+    /// Returns all events received since creation or the last `clear`.
+    pub fn events(&self) -> Vec<SinkEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.events.lock().unwrap().clear();
+    }
+}
+
+impl KeySink for MockSink {
+    fn press(&mut self, key: &UinputKey) -> error::Result<()> {
+        if *self.failing.lock().unwrap() {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "device is gone").into());
+        }
+        self.events.lock().unwrap().push(SinkEvent::Press(*key));
+        Ok(())
+    }
+
+    fn release(&mut self, key: &UinputKey) -> error::Result<()> {
+        self.events.lock().unwrap().push(SinkEvent::Release(*key));
+        Ok(())
+    }
+
+    fn synchronize(&mut self) -> error::Result<()> {
+        self.events.lock().unwrap().push(SinkEvent::Synchronize);
+        Ok(())
+    }
+
+    fn repeat(&mut self, key: &UinputKey) -> error::Result<()> {
+        self.events.lock().unwrap().push(SinkEvent::Repeat(*key));
+        Ok(())
+    }
+
+    fn leds(&mut self) -> error::Result<Vec<(Lock, bool)>> {
+        Ok(::std::mem::replace(&mut *self.leds.lock().unwrap(), Vec::new()))
+    }
+}
+
+/// Drives a handler like `Keyboard` does, but with a `MockKeyboard` and simulated time.
+///
+/// Time only passes when calling `advance` or `tick`, which call `handle_time` in the handler's
+/// interval, so runs are deterministic.
+pub struct Harness<H: KeyboardHandler> {
+    handler: H,
+    keyboard: MockKeyboard,
+    now: Instant,
+    next_tick: Option<Instant>,
+}
+
+impl<H: KeyboardHandler> Harness<H> {
+    pub fn new(handler: H) -> Harness<H> {
+        let now = Instant::now();
+        let mut harness = Harness {
+            handler: handler,
+            keyboard: MockKeyboard::new(),
+            now: now,
+            next_tick: None,
+        };
+        harness.next_tick = harness.after_interval(now);
+        harness
+    }
+
+    /// Returns when `handle_time` is due after the given tick. A zero interval counts as a
+    /// millisecond, so `advance` doesn't loop forever.
+    fn after_interval(&self, tick: Instant) -> Option<Instant> {
+        self.handler.interval().map(|interval| tick + interval.max(Duration::from_millis(1)))
+    }
+
+    pub fn init(&mut self) -> UsbResult<()> {
+        self.handler.init(&mut self.keyboard)
+    }
+
+    /// Passes the event to the handler if it accepts it and returns whether it did.
+    pub fn event(&mut self, evt: KeyEvent) -> UsbResult<bool> {
+        if !self.handler.accept_key(&evt) {
+            return Ok(false);
+        }
+        try!(self.handler.handle_key(&evt, self.now, &mut self.keyboard));
+        Ok(true)
+    }
+
+    pub fn press(&mut self, key: Key) -> UsbResult<bool> {
+        self.event(KeyEvent::KeyPressed(key))
+    }
+
+    pub fn release(&mut self, key: Key) -> UsbResult<bool> {
+        self.event(KeyEvent::KeyReleased(key))
+    }
+
+    /// Presses and immediately releases the given key.
+    pub fn click(&mut self, key: Key) -> UsbResult<()> {
+        try!(self.press(key.clone()));
+        try!(self.release(key));
+        Ok(())
+    }
+
+    /// Lets the given time pass, calling `handle_time` whenever the handler's interval elapses.
+    pub fn advance(&mut self, duration: Duration) -> UsbResult<()> {
+        let end = self.now + duration;
+        while let Some(tick) = self.next_tick {
+            if tick > end {
+                break;
+            }
+            self.now = tick;
+            self.next_tick = self.after_interval(tick);
+            try!(self.handler.handle_time(tick, &mut self.keyboard));
+        }
+        self.now = end;
+        Ok(())
+    }
+
+    /// Lets time pass up to the next call of `handle_time`. Does nothing if the handler
+    /// doesn't have an interval.
+    pub fn tick(&mut self) -> UsbResult<()> {
+        match self.next_tick {
+            Some(tick) => {
+                let duration = tick.duration_since(self.now);
+                self.advance(duration)
+            },
+            None => Ok(()),
+        }
+    }
+
+    pub fn now(&self) -> Instant {
+        self.now
+    }
+
+    pub fn keyboard(&self) -> &MockKeyboard {
+        &self.keyboard
+    }
+
+    pub fn keyboard_mut(&mut self) -> &mut MockKeyboard {
+        &mut self.keyboard
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    pub fn handler_mut(&mut self) -> &mut H {
+        &mut self.handler
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use libusb::Result as UsbResult;
+    use g910::*;
+
+    use backend::{KeyboardHandler, Leds};
+    use super::{Call, Harness};
+
+    /// Counts its ticks and lights pressed keys white.
+    struct Counter {
+        interval: Option<Duration>,
+        ticks: u32,
+    }
+
+    impl KeyboardHandler for Counter {
+        #[allow(unused_variables)]
+        fn accept_key(&self, evt: &KeyEvent) -> bool {
+            true
+        }
+
+        #[allow(unused_variables)]
+        fn handle_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+            match evt {
+                &KeyEvent::KeyPressed(ref key) =>
+                    keyboard.set_key_colors(vec![KeyColor::new(key.clone(), Color::new(255, 255, 255))]),
+                &KeyEvent::KeyReleased(_) => Ok(()),
+            }
+        }
+
+        fn interval(&self) -> Option<Duration> {
+            self.interval
+        }
+
+        #[allow(unused_variables)]
+        fn handle_time(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+            self.ticks += 1;
+            Ok(())
+        }
+    }
+
+    fn counter(interval: Option<Duration>) -> Harness<Counter> {
+        Harness::new(Counter { interval: interval, ticks: 0 })
+    }
+
+    #[test]
+    fn records_colors() {
+        let mut harness = counter(None);
+        let a = Key::Standard(StandardKey::A);
+        assert!(harness.press(a.clone()).unwrap());
+        assert_eq!(harness.keyboard().color(&a), Some(Color::new(255, 255, 255)));
+        assert_eq!(harness.keyboard().color(&Key::Standard(StandardKey::B)), Some(Color::new(0, 0, 0)));
+        assert_eq!(harness.keyboard().calls(), &[Call::SetKeyColors(vec![(a, Color::new(255, 255, 255))])][..]);
+    }
+
+    #[test]
+    fn ticks_in_interval() {
+        let mut harness = counter(Some(Duration::from_millis(100)));
+        let start = harness.now();
+        harness.advance(Duration::from_millis(250)).unwrap();
+        assert_eq!(harness.handler().ticks, 2);
+        harness.tick().unwrap();
+        assert_eq!(harness.handler().ticks, 3);
+        assert_eq!(harness.now().duration_since(start), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn zero_interval_ticks_every_millisecond() {
+        let mut harness = counter(Some(Duration::from_millis(0)));
+        harness.advance(Duration::from_millis(10)).unwrap();
+        assert_eq!(harness.handler().ticks, 10);
+    }
+}
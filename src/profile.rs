@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use libusb::Result as UsbResult;
+use g910::*;
+
+use backend::{self, KeyboardHandler, Leds};
+
+/// A set of handlers active together, e.g. a lighting effect and a `UinputHandler` with its own
+/// remapping and macros.
+pub struct Profile {
+    handlers: Vec<Box<KeyboardHandler>>,
+    /// When each handler's `handle_time` has been called last.
+    ticks: Vec<Option<Instant>>,
+    color: Color,
+    initialized: bool,
+}
+
+impl Profile {
+    /// Creates a profile without any handlers, whose M-key is lit white.
+    pub fn new() -> Profile {
+        Profile {
+            handlers: Vec::new(),
+            ticks: Vec::new(),
+            color: Color::new(255, 255, 255),
+            initialized: false,
+        }
+    }
+
+    /// Adds a handler to the profile. Key events are passed to all handlers accepting them, in
+    /// the order they have been added.
+    pub fn handler<H: KeyboardHandler + 'static>(mut self, handler: H) -> Profile {
+        self.handlers.push(Box::new(handler));
+        self.ticks.push(None);
+        self
+    }
+
+    /// Sets the color the M-key is lit with while the profile is active.
+    pub fn color(mut self, color: Color) -> Profile {
+        self.color = color;
+        self
+    }
+}
+
+/// Switches between three profiles with the M1, M2 and M3 keys.
+///
+/// Only the active profile gets key events and time, except releases which go to the profile
+/// that got the press, so switching doesn't leave keys stuck. Handlers are initialized the first
+/// time their profile becomes active and redrawn on later switches. The handlers of the profile
+/// switched away from are suspended, so e.g. a `UinputHandler` releases the keys of a macro
+/// being played back instead of leaving them held until switching back. The active M-key is lit in
+/// the color of its profile, the other two are turned off.
+pub struct ProfileManager {
+    profiles: Vec<Profile>,
+    active: usize,
+    handle: Arc<Mutex<usize>>,
+    /// The profile that got the press of every pressed key.
+    pressed: HashMap<Key, usize>,
+}
+
+impl ProfileManager {
+    /// Creates a manager for the profiles of M1, M2 and M3, starting with M1.
+    pub fn new(m1: Profile, m2: Profile, m3: Profile) -> ProfileManager {
+        ProfileManager {
+            profiles: vec![m1, m2, m3],
+            active: 0,
+            handle: Arc::new(Mutex::new(0)),
+            pressed: HashMap::new(),
+        }
+    }
+
+    /// Returns the index of the active profile, 0 for M1.
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    /// Returns a handle to the index of the active profile, which can be changed while the
+    /// manager is running to switch profiles. The switch happens with the next key event or tick.
+    pub fn profile_handle(&self) -> Arc<Mutex<usize>> {
+        self.handle.clone()
+    }
+
+    fn activate(&mut self, index: usize, keyboard: &mut Leds) -> UsbResult<()> {
+        if index != self.active {
+            try!(self.suspend());
+        }
+        self.active = index;
+        *self.handle.lock().unwrap() = index;
+        let color = self.profiles[index].color;
+        try!(keyboard.set_all_colors(Color::new(0, 0, 0)));
+        try!(keyboard.set_key_colors(bank_colors(index, color)));
+        let profile = &mut self.profiles[index];
+        let mut leds = ProfileLeds { leds: keyboard, visible: true, active: index, color: color };
+        for handler in &mut profile.handlers {
+            if profile.initialized {
+                try!(handler.redraw(&mut leds));
+            } else {
+                try!(handler.init(&mut leds));
+            }
+        }
+        profile.initialized = true;
+        Ok(())
+    }
+
+    /// Switches to the profile set through the handle, if it has been changed.
+    fn sync_handle(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        let index = *self.handle.lock().unwrap();
+        if index != self.active && index < self.profiles.len() {
+            try!(self.activate(index, keyboard));
+        }
+        Ok(())
+    }
+}
+
+fn bank_index(key: &Key) -> Option<usize> {
+    match key {
+        &Key::Gaming(GamingKey::M1) => Some(0),
+        &Key::Gaming(GamingKey::M2) => Some(1),
+        &Key::Gaming(GamingKey::M3) => Some(2),
+        _ => None,
+    }
+}
+
+fn bank_colors(active: usize, color: Color) -> Vec<KeyColor> {
+    [GamingKey::M1, GamingKey::M2, GamingKey::M3].iter().enumerate()
+        .map(|(i, &key)| {
+            let color = if i == active { color } else { Color::new(0, 0, 0) };
+            KeyColor::new(Key::Gaming(key), color)
+        })
+        .collect()
+}
+
+/// Passes the colors of a profile's handlers through, except for the M-keys which show the
+/// active profile. Drawing of handlers of an inactive profile is discarded.
+struct ProfileLeds<'a> {
+    leds: &'a mut Leds,
+    visible: bool,
+    active: usize,
+    color: Color,
+}
+
+impl<'a> Leds for ProfileLeds<'a> {
+    fn set_all_colors(&mut self, color: Color) -> UsbResult<()> {
+        if !self.visible {
+            return Ok(());
+        }
+        try!(self.leds.set_all_colors(color));
+        self.leds.set_key_colors(bank_colors(self.active, self.color))
+    }
+
+    fn set_key_colors(&mut self, key_colors: Vec<KeyColor>) -> UsbResult<()> {
+        let key_colors: Vec<_> = key_colors.into_iter()
+            .filter(|kc| bank_index(&kc.key).is_none())
+            .collect();
+        if !self.visible || key_colors.is_empty() {
+            return Ok(());
+        }
+        self.leds.set_key_colors(key_colors)
+    }
+
+    fn color(&self, key: &Key) -> Option<Color> {
+        self.leds.color(key)
+    }
+}
+
+impl KeyboardHandler for ProfileManager {
+    fn init(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        let index = self.active;
+        self.activate(index, keyboard)
+    }
+
+    fn redraw(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        let index = self.active;
+        self.activate(index, keyboard)
+    }
+
+    fn accept_key(&self, evt: &KeyEvent) -> bool {
+        let key = match evt {
+            &KeyEvent::KeyPressed(ref k) => k,
+            &KeyEvent::KeyReleased(ref k) => k,
+        };
+        bank_index(key).is_some() || self.pressed.contains_key(key)
+            || self.profiles[self.active].handlers.iter().any(|h| h.accept_key(evt))
+    }
+
+    fn handle_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        try!(self.sync_handle(keyboard));
+        let index = match evt {
+            &KeyEvent::KeyPressed(ref k) => {
+                if let Some(index) = bank_index(k) {
+                    if index != self.active {
+                        try!(self.activate(index, keyboard));
+                    }
+                    return Ok(());
+                }
+                self.pressed.insert(k.clone(), self.active);
+                self.active
+            },
+            &KeyEvent::KeyReleased(ref k) => {
+                if bank_index(k).is_some() {
+                    return Ok(());
+                }
+                self.pressed.remove(k).unwrap_or(self.active)
+            },
+        };
+        let mut leds = ProfileLeds {
+            leds: keyboard,
+            visible: index == self.active,
+            active: self.active,
+            color: self.profiles[self.active].color,
+        };
+        for handler in &mut self.profiles[index].handlers {
+            if handler.accept_key(evt) {
+                try!(handler.handle_key(evt, now, &mut leds));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the shortest interval of all handlers, the others are called at their interval
+    /// rounded up to a multiple of it.
+    fn interval(&self) -> Option<Duration> {
+        self.profiles.iter()
+            .flat_map(|p| p.handlers.iter())
+            .filter_map(|h| h.interval())
+            .min()
+    }
+
+    fn suspend(&mut self) -> UsbResult<()> {
+        for handler in &mut self.profiles[self.active].handlers {
+            try!(handler.suspend());
+        }
+        Ok(())
+    }
+
+    fn handle_time(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        try!(self.sync_handle(keyboard));
+        let active = self.active;
+        let profile = &mut self.profiles[active];
+        let mut leds = ProfileLeds { leds: keyboard, visible: true, active: active, color: profile.color };
+        for (handler, tick) in profile.handlers.iter_mut().zip(profile.ticks.iter_mut()) {
+            let interval = match handler.interval() {
+                Some(interval) => interval,
+                None => continue,
+            };
+            if tick.map_or(false, |last| now.duration_since(last) < interval) {
+                continue;
+            }
+            *tick = Some(now);
+            try!(handler.handle_time(now, &mut leds));
+        }
+        Ok(())
+    }
+}
+
+impl From<ProfileManager> for Handler {
+    fn from(handler: ProfileManager) -> Handler {
+        backend::into_handler(handler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use g910::*;
+    use uinput::event::{Keyboard as UinputKey};
+    use uinput::event::keyboard::Key as UinputStandardKey;
+
+    use mock::{Harness, MockSink, SinkEvent};
+    use u_input::UinputHandler;
+    use super::{Profile, ProfileManager};
+
+    #[test]
+    fn switching_releases_held_keys() {
+        let sink = MockSink::new();
+        let m1 = Profile::new().color(Color::new(255, 0, 0)).handler(UinputHandler::with_sink(sink.clone()));
+        let m2 = Profile::new().color(Color::new(0, 255, 0));
+        let mut harness = Harness::new(ProfileManager::new(m1, m2, Profile::new()));
+        harness.init().unwrap();
+        assert_eq!(harness.keyboard().color(&Key::Gaming(GamingKey::M1)), Some(Color::new(255, 0, 0)));
+        harness.press(Key::Standard(StandardKey::A)).unwrap();
+        sink.clear();
+        harness.press(Key::Gaming(GamingKey::M2)).unwrap();
+        assert_eq!(harness.handler().active(), 1);
+        assert_eq!(harness.keyboard().color(&Key::Gaming(GamingKey::M1)), Some(Color::new(0, 0, 0)));
+        assert_eq!(harness.keyboard().color(&Key::Gaming(GamingKey::M2)), Some(Color::new(0, 255, 0)));
+        assert_eq!(sink.events(), vec![
+            SinkEvent::Release(UinputKey::Key(UinputStandardKey::A)),
+            SinkEvent::Synchronize,
+        ]);
+    }
+}
@@ -0,0 +1,174 @@
+use std::time::{Duration, Instant};
+use libusb::Result as UsbResult;
+use g910::*;
+
+use backend::{self, secs, KeyboardHandler, Leds};
+use geometry::{Geometry, Variant};
+
+/// Milliseconds between two frames while ripples are running.
+const FRAME_INTERVAL: u64 = 30;
+
+/// Lets colored rings expand from pressed keys across the board.
+///
+/// A ring grows with the configured speed and fades out over its lifetime and with the
+/// distance it has travelled. Overlapping rings add up. Keys are positioned by their physical
+/// location, see `geometry`.
+pub struct RippleHandler {
+    geometry: Geometry,
+    /// The lightable keys with their centers.
+    keys: Vec<(Key, (f64, f64))>,
+    /// Origin and start of every running ripple.
+    ripples: Vec<((f64, f64), Instant)>,
+    color: Color,
+    /// Millimetres per second.
+    speed: f64,
+    /// Thickness of the ring in millimetres.
+    width: f64,
+    duration: Duration,
+    /// Whether the last frame lit any key, so the board is turned off once after the last
+    /// ripple ended.
+    lit: bool,
+}
+
+impl RippleHandler {
+    /// Creates a handler with cyan ripples crossing the board in about 600ms.
+    pub fn new() -> RippleHandler {
+        RippleHandler::with_geometry(Geometry::new(Variant::Iso))
+    }
+
+    /// Creates a handler positioning keys by the given geometry, e.g. of an ANSI keyboard.
+    pub fn with_geometry(geometry: Geometry) -> RippleHandler {
+        RippleHandler {
+            keys: geometry.lightable(),
+            geometry: geometry,
+            ripples: Vec::new(),
+            color: Color::new(0, 160, 255),
+            speed: 750.0,
+            width: 30.0,
+            duration: Duration::from_millis(600),
+            lit: false,
+        }
+    }
+
+    /// Sets the color of the rings at full intensity, cyan by default. Rings fade towards black
+    /// at their edges and as they spread.
+    pub fn color(mut self, color: Color) -> RippleHandler {
+        self.color = color;
+        self
+    }
+
+    /// Sets the speed of the rings in millimetres per second.
+    pub fn speed(mut self, speed: f64) -> RippleHandler {
+        self.speed = speed;
+        self
+    }
+
+    /// Sets the thickness of the rings in millimetres.
+    pub fn width(mut self, width: f64) -> RippleHandler {
+        self.width = width;
+        self
+    }
+
+    /// Sets how long a ring lasts until it has faded out completely.
+    pub fn duration(mut self, duration: Duration) -> RippleHandler {
+        self.duration = duration;
+        self
+    }
+
+    /// Returns the intensity of a key at the given position in [0, 1].
+    fn intensity(&self, (x, y): (f64, f64), now: Instant) -> f64 {
+        let duration = secs(self.duration);
+        let mut intensity = 0.0;
+        for &((ox, oy), start) in &self.ripples {
+            let t = secs(now.duration_since(start));
+            let distance = ((x - ox).powi(2) + (y - oy).powi(2)).sqrt();
+            let ring = 1.0 - (distance - t * self.speed).abs() / self.width;
+            if ring <= 0.0 {
+                continue;
+            }
+            let fade = 1.0 - t / duration;
+            intensity += ring * fade / (1.0 + distance / 400.0);
+        }
+        intensity.max(0.0).min(1.0)
+    }
+
+    fn draw(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        let duration = self.duration;
+        self.ripples.retain(|&(_, start)| now.duration_since(start) < duration);
+        if self.ripples.is_empty() && !self.lit {
+            return Ok(());
+        }
+        let key_colors: Vec<_> = self.keys.iter()
+            .map(|&(ref key, pos)| {
+                let i = self.intensity(pos, now);
+                let color = Color::new((self.color.red as f64 * i) as u8,
+                    (self.color.green as f64 * i) as u8, (self.color.blue as f64 * i) as u8);
+                KeyColor::new(key.clone(), color)
+            })
+            .collect();
+        self.lit = !self.ripples.is_empty();
+        keyboard.set_key_colors(key_colors)
+    }
+}
+
+impl KeyboardHandler for RippleHandler {
+    fn init(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        keyboard.set_all_colors(Color::new(0, 0, 0))
+    }
+
+    fn accept_key(&self, evt: &KeyEvent) -> bool {
+        match evt {
+            &KeyEvent::KeyPressed(ref key) => self.geometry.get(key).is_some(),
+            &KeyEvent::KeyReleased(_) => false,
+        }
+    }
+
+    fn handle_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        if let &KeyEvent::KeyPressed(ref key) = evt {
+            if let Some(pos) = self.geometry.center(key) {
+                self.ripples.push((pos, now));
+            }
+        }
+        self.draw(now, keyboard)
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(FRAME_INTERVAL))
+    }
+
+    fn handle_time(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        self.draw(now, keyboard)
+    }
+}
+
+impl From<RippleHandler> for Handler {
+    fn from(handler: RippleHandler) -> Handler {
+        backend::into_handler(handler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use g910::*;
+
+    use mock::Harness;
+    use super::RippleHandler;
+
+    #[test]
+    fn ring_expands_and_fades() {
+        let mut harness = Harness::new(RippleHandler::new());
+        let (g, s) = (Key::Standard(StandardKey::G), Key::Standard(StandardKey::S));
+        let black = Some(Color::new(0, 0, 0));
+        harness.init().unwrap();
+        harness.press(g.clone()).unwrap();
+        assert_eq!(harness.keyboard().color(&g), Some(Color::new(0, 160, 255)));
+        assert_eq!(harness.keyboard().color(&s), black);
+        // the ring has travelled about four keys by now
+        harness.advance(Duration::from_millis(100)).unwrap();
+        assert_eq!(harness.keyboard().color(&g), black);
+        assert!(harness.keyboard().color(&s) != black);
+        harness.advance(Duration::from_millis(600)).unwrap();
+        assert!(harness.keyboard().frame().values().all(|&c| c == Color::new(0, 0, 0)));
+    }
+}
@@ -1,17 +1,28 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::collections::VecDeque;
 use libusb::Result as UsbResult;
-use rand::Rng;
+use rand::{Rng, SeedableRng, XorShiftRng};
 use rand;
 use g910::*;
 use g910::StandardKey::*;
 
-const FIELD: [[StandardKey; 13]; 4] = [
-    [Circumflex,_1,_2,_3,_4,_5,_6,_7,_8,_9,_0,Sz,Tick],
-    [Tab,Q,W,E,R,T,Z,U,I,O,P,Uuml,Plus],
-    [CapsLock,A,S,D,F,G,H,J,K,L,Ouml,Auml,Sharp],
-    [LeftShift,SmallerThan,Y,X,C,V,B,N,M,Comma,Dot,Minus,RightShift]
-];
+use backend::{self, KeyboardHandler, Leds};
+use geometry::{Geometry, Variant};
+
+/// Returns the rows of the main block from the number row to the shift row, cut to the length
+/// of the shortest one.
+fn field(geometry: &Geometry) -> Vec<Vec<StandardKey>> {
+    let right = geometry.get(&Key::Standard(Backspace)).map_or(0.0, |g| g.x + g.width);
+    let rows: Vec<Vec<StandardKey>> = (1..5).map(|row| geometry.row(row).into_iter()
+        .filter(|g| g.x < right)
+        .filter_map(|g| match g.key {
+            Key::Standard(key) => Some(key),
+            _ => Option::None,
+        })
+        .collect()).collect();
+    let width = rows.iter().map(Vec::len).min().unwrap_or(0);
+    rows.into_iter().map(|mut row| { row.truncate(width); row }).collect()
+}
 
 #[derive(Clone, Copy)]
 enum Direction {
@@ -24,21 +35,36 @@ enum State {
 }
 
 pub struct Snake {
+    field: Vec<Vec<StandardKey>>,
     snake: VecDeque<(u8, u8)>,
     apple: (u8, u8),
     state: State,
     actions: VecDeque<Direction>,
     last_direction: Direction,
+    rng: XorShiftRng,
 }
 
 impl Snake {
     pub fn new() -> Snake {
+        Snake::with_rng(rand::thread_rng().gen())
+    }
+
+    /// Creates a Snake placing its apples in the same order for the same seed.
+    ///
+    /// The seed must not be all zeroes.
+    pub fn with_seed(seed: [u32; 4]) -> Snake {
+        Snake::with_rng(XorShiftRng::from_seed(seed))
+    }
+
+    fn with_rng(rng: XorShiftRng) -> Snake {
         Snake {
+            field: field(&Geometry::new(Variant::Iso)),
             snake: VecDeque::new(),
             apple: (0,0),
             state: State::Running,
             actions: VecDeque::new(),
             last_direction: Direction::Right,
+            rng: rng,
         }
     }
 
@@ -47,27 +73,28 @@ impl Snake {
         let delta = 155.0 / len as f64;
         let mut vec: Vec<_> = self.snake.iter().skip(1).enumerate().map(|(i,&(x,y))| {
             let u = 100 + (delta * (len-i) as f64) as u8;
-            KeyColor::new(Key::Standard(FIELD[y as usize][x as usize]),Color::new(0,0,u))
+            KeyColor::new(Key::Standard(self.field[y as usize][x as usize]),Color::new(0,0,u))
         }).collect();
         let (x,y) = self.snake[0];
-        vec.push(KeyColor::new(Key::Standard(FIELD[y as usize][x as usize]), Color::new(0xe9,0x1e,0x63)));
+        vec.push(KeyColor::new(Key::Standard(self.field[y as usize][x as usize]), Color::new(0xe9,0x1e,0x63)));
         let (ax, ay) = self.apple;
-        vec.push(KeyColor::new(Key::Standard(FIELD[ay as usize][ax as usize]), Color::new(0,255,0)));
+        vec.push(KeyColor::new(Key::Standard(self.field[ay as usize][ax as usize]), Color::new(0,255,0)));
         vec
     }
 
     fn new_apple(&mut self) {
-        let mut rng = rand::thread_rng();
         loop {
-            let (ax, ay) = (rng.gen::<u8>() % FIELD[0].len() as u8, rng.gen::<u8>() % FIELD.len() as u8);
+            let (ax, ay) = (self.rng.gen::<u8>() % self.field[0].len() as u8, self.rng.gen::<u8>() % self.field.len() as u8);
             if !self.snake.iter().any(|&(x,y)| x==ax && y==ay) {
                 self.apple = (ax, ay);
                 break;
             }
         }
     }
+}
 
-    fn init(&mut self, keyboard: &mut Keyboard) -> UsbResult<()> {
+impl KeyboardHandler for Snake {
+    fn init(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
         self.snake.push_front((1, 2));
         self.snake.push_front((2, 2));
         self.snake.push_front((3, 2));
@@ -76,6 +103,10 @@ impl Snake {
         keyboard.set_key_colors(self.to_key_colors())
     }
 
+    fn redraw(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        keyboard.set_key_colors(self.to_key_colors())
+    }
+
     fn accept_key(&self, evt: &KeyEvent) -> bool {
         if self.state == State::Running {
             match evt {
@@ -93,7 +124,8 @@ impl Snake {
         }
     }
 
-    fn handle_key(&mut self, evt: &KeyEvent, keyboard: &mut Keyboard) -> UsbResult<()> {
+    #[allow(unused_variables)]
+    fn handle_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
         if self.state == State::Running {
             if self.actions.len() >= 2 {
                 self.actions.pop_front();
@@ -107,12 +139,18 @@ impl Snake {
             });
             Ok(())
         } else {
-            ::std::mem::replace(self, Snake::new());
+            let rng = self.rng.clone();
+            *self = Snake::with_rng(rng);
             self.init(keyboard)
         }
     }
 
-    fn handle_time(&mut self, keyboard: &mut Keyboard) -> UsbResult<()> {
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(350))
+    }
+
+    #[allow(unused_variables)]
+    fn handle_time(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
         if self.state == State::Stop {
             return Ok(());
         }
@@ -128,8 +166,8 @@ impl Snake {
             Direction::Left => (x-1, y),
             Direction::Right => (x+1, y),
         };
-        let xlen = FIELD[0].len() as i16;
-        let ylen = FIELD.len() as i16;
+        let xlen = self.field[0].len() as i16;
+        let ylen = self.field.len() as i16;
         nx = ((nx % xlen) + xlen) % xlen;
         ny = ((ny % ylen) + ylen) % ylen;
         let (nx, ny) = (nx as u8, ny as u8);
@@ -150,7 +188,7 @@ impl Snake {
             try!(keyboard.set_all_colors(Color::new(255,0,0)));
             let mut vec = Vec::new();
             vec.push(KeyColor::new(Key::Standard(StandardKey::NumReturn), Color::new(0,0,255)));
-            vec.push(KeyColor::new(Key::Standard(FIELD[ny as usize][nx as usize]), Color::new(255,165,0)));
+            vec.push(KeyColor::new(Key::Standard(self.field[ny as usize][nx as usize]), Color::new(255,165,0)));
             try!(keyboard.set_key_colors(self.to_key_colors()));
             return keyboard.set_key_colors(vec)
         }
@@ -163,7 +201,7 @@ impl Snake {
         let mut vec = self.to_key_colors();
         if let Some((px,py)) = popped {
             if !self.snake.iter().any(|&(x,y)| x==px && y==py) {
-                vec.push(KeyColor::new(Key::Standard(FIELD[py as usize][px as usize]), Color::new(0,0,0)));
+                vec.push(KeyColor::new(Key::Standard(self.field[py as usize][px as usize]), Color::new(0,0,0)));
             }
         }
         keyboard.set_key_colors(vec)
@@ -172,12 +210,33 @@ impl Snake {
 
 impl From<Snake> for Handler {
     fn from(handler: Snake) -> Handler {
-        HandlerBuilder::new(handler)
-            .init_fn(|handler, keyboard| handler.init(keyboard))
-            .accept_key_fn(|handler, evt| handler.accept_key(evt))
-            .handle_key_fn(|handler, evt, keyboard| handler.handle_key(evt, keyboard))
-            .handle_time_fn(|handler, _, keyboard| handler.handle_time(keyboard), Duration::from_millis(350))
-            .build()
+        backend::into_handler(handler)
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use g910::*;
+
+    use mock::Harness;
+    use super::Snake;
+
+    fn head() -> Option<Color> {
+        Some(Color::new(0xe9, 0x1e, 0x63))
+    }
+
+    #[test]
+    fn moves_right_and_turns() {
+        let mut harness = Harness::new(Snake::with_seed([1, 2, 3, 4]));
+        harness.init().unwrap();
+        assert_eq!(harness.keyboard().color(&Key::Standard(StandardKey::D)), head());
+        harness.tick().unwrap();
+        assert_eq!(harness.keyboard().color(&Key::Standard(StandardKey::F)), head());
+        assert!(harness.press(Key::Standard(StandardKey::Up)).unwrap());
+        assert!(!harness.press(Key::Standard(StandardKey::A)).unwrap());
+        harness.advance(Duration::from_millis(350)).unwrap();
+        assert_eq!(harness.keyboard().color(&Key::Standard(StandardKey::R)), head());
+    }
+}
@@ -0,0 +1,175 @@
+//! Golden-frame testing of lighting effects.
+//!
+//! A script of steps is run through a `mock::Harness`, the colors of all keys are captured after
+//! each step and the result is compared against a golden file:
+//!
+//! ```ignore
+//! let mut harness = Harness::new(Snake::with_seed([1, 2, 3, 4]));
+//! let frames = snapshot::record(&mut harness, &[
+//!     Step::Init,
+//!     Step::Press(Key::Standard(StandardKey::Down)),
+//!     Step::Tick,
+//! ]).unwrap();
+//! snapshot::assert_golden("tests/golden/snake.txt", &frames);
+//! ```
+//!
+//! Golden files are only ever written when running with the environment variable `UPDATE_GOLDEN`
+//! set, which creates or overwrites all of them, after which the diff can be reviewed.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::Duration;
+use libusb::Result as UsbResult;
+use g910::*;
+
+use backend::KeyboardHandler;
+use mock::Harness;
+
+/// A step of a scripted event sequence.
+#[derive(Debug, Clone)]
+pub enum Step {
+    Init,
+    Press(Key),
+    Release(Key),
+    /// Press immediately followed by release, captured as a single step.
+    Click(Key),
+    Advance(Duration),
+    Tick,
+}
+
+/// Renders the given frame as text, one `<key> #rrggbb` line per key sorted by key.
+pub fn render(frame: &HashMap<Key, Color>) -> String {
+    let mut lines: Vec<_> = frame.iter()
+        .map(|(k, c)| format!("{:?} #{:02x}{:02x}{:02x}\n", k, c.red, c.green, c.blue))
+        .collect();
+    lines.sort();
+    lines.concat()
+}
+
+/// Runs the steps and returns the rendered frame after each of them, preceded by a header
+/// naming the step.
+pub fn record<H: KeyboardHandler>(harness: &mut Harness<H>, steps: &[Step]) -> UsbResult<String> {
+    let mut out = String::new();
+    for (i, step) in steps.iter().enumerate() {
+        match step {
+            &Step::Init => try!(harness.init()),
+            &Step::Press(ref key) => { try!(harness.press(key.clone())); },
+            &Step::Release(ref key) => { try!(harness.release(key.clone())); },
+            &Step::Click(ref key) => try!(harness.click(key.clone())),
+            &Step::Advance(duration) => try!(harness.advance(duration)),
+            &Step::Tick => try!(harness.tick()),
+        }
+        out.push_str(&format!("== {}: {:?}\n", i, step));
+        out.push_str(&render(harness.keyboard().frame()));
+    }
+    Ok(out)
+}
+
+/// Compares the output with the golden file at the given path.
+///
+/// The golden file is written instead if `UPDATE_GOLDEN` is set.
+///
+/// # Panics
+///
+/// Panics on the first line differing from the golden file or if it is missing or can't be read
+/// or written.
+pub fn assert_golden<P: AsRef<Path>>(path: P, actual: &str) {
+    let path = path.as_ref();
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        File::create(path).and_then(|mut f| f.write_all(actual.as_bytes()))
+            .unwrap_or_else(|e| panic!("can't write golden file {}: {}", path.display(), e));
+        return;
+    }
+    if !path.exists() {
+        panic!("golden file {} is missing, run with UPDATE_GOLDEN=1 to create it", path.display());
+    }
+    let mut expected = String::new();
+    File::open(path).and_then(|mut f| f.read_to_string(&mut expected))
+        .unwrap_or_else(|e| panic!("can't read golden file {}: {}", path.display(), e));
+    if expected == actual {
+        return;
+    }
+    let mut header = "";
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    for line in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => {
+                if a.starts_with("== ") {
+                    header = a;
+                }
+            },
+            (e, a) => panic!("output differs from golden file {} at line {} (after {:?}):\n\
+                expected: {:?}\n  actual: {:?}\nrerun with UPDATE_GOLDEN=1 to accept the new output",
+                path.display(), line, header, e, a),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use g910::*;
+
+    use flash::FlashHandler;
+    use heatmap::HeatmapHandler;
+    use mock::Harness;
+    use snake::Snake;
+    use super::{assert_golden, record, Step};
+
+    fn golden(name: &str) -> String {
+        format!("{}/tests/golden/{}.txt", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn key(key: StandardKey) -> Key {
+        Key::Standard(key)
+    }
+
+    #[test]
+    fn snake() {
+        let mut harness = Harness::new(Snake::with_seed([1, 2, 3, 4]));
+        let frames = record(&mut harness, &[
+            Step::Init,
+            Step::Tick,
+            Step::Press(key(StandardKey::Up)),
+            Step::Tick,
+            Step::Press(key(StandardKey::Left)),
+            Step::Tick,
+            Step::Tick,
+        ]).unwrap();
+        assert_golden(golden("snake"), &frames);
+    }
+
+    #[test]
+    fn heatmap() {
+        let mut harness = Harness::new(HeatmapHandler::new());
+        let frames = record(&mut harness, &[
+            Step::Init,
+            Step::Click(key(StandardKey::A)),
+            Step::Click(key(StandardKey::A)),
+            Step::Click(key(StandardKey::S)),
+            Step::Click(key(StandardKey::Space)),
+        ]).unwrap();
+        assert_golden(golden("heatmap"), &frames);
+    }
+
+    #[test]
+    fn flash() {
+        let mut harness = Harness::new(FlashHandler::new().per_key(true));
+        let frames = record(&mut harness, &[
+            Step::Init,
+            Step::Press(key(StandardKey::A)),
+            Step::Press(key(StandardKey::S)),
+            Step::Release(key(StandardKey::A)),
+            Step::Advance(Duration::from_millis(100)),
+            Step::Release(key(StandardKey::S)),
+        ]).unwrap();
+        assert_golden(golden("flash"), &frames);
+    }
+}
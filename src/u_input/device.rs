@@ -0,0 +1,174 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::slice;
+use std::time::Duration;
+use libc;
+use uinput::event::{Code, Kind, Keyboard as UinputKey};
+
+use config::Repeat;
+use error;
+use super::{KeySink, Lock};
+
+const UI_DEV_CREATE: u64 = 0x5501;
+const UI_DEV_DESTROY: u64 = 0x5502;
+const UI_SET_EVBIT: u64 = 0x4004_5564;
+const UI_SET_KEYBIT: u64 = 0x4004_5565;
+const UI_SET_LEDBIT: u64 = 0x4004_5569;
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_LED: u16 = 0x11;
+const EV_REP: u16 = 0x14;
+const SYN_REPORT: u16 = 0x00;
+const REP_DELAY: u16 = 0x00;
+const REP_PERIOD: u16 = 0x01;
+const LED_NUML: u16 = 0x00;
+const LED_CAPSL: u16 = 0x01;
+const LED_SCROLLL: u16 = 0x02;
+const BUS_VIRTUAL: u16 = 0x06;
+
+/// Key codes up to the first button, the range of keyboard keys.
+const BTN_MISC: u16 = 0x100;
+/// Key codes between the buttons and the joystick buttons, e.g. KEY_OK and KEY_FN.
+const KEY_OK: u16 = 0x160;
+const BTN_TRIGGER_HAPPY: u16 = 0x2c0;
+
+/// A virtual keyboard created through uinput, which also has the lock LEDs.
+///
+/// The uinput crate opens the device node write-only and can't enable EV_LED, so this creates
+/// the device itself. The desktop then sets the LEDs of the virtual keyboard like of any other
+/// keyboard, which can be read with `KeySink::leds`.
+pub struct VirtualDevice {
+    file: File,
+}
+
+impl VirtualDevice {
+    /// Creates a keyboard with all keys and the lock LEDs through the given uinput device node.
+    ///
+    /// With a repeat, the device has EV_REP and the input core repeats its last pressed key with
+    /// the repeat's delay and period, emitting events with value 2. Sessions using libinput,
+    /// i.e. Wayland and most X sessions, ignore these events and repeat keys with the desktop's
+    /// settings instead, so the repeat only affects e.g. the console and programs reading the
+    /// device directly.
+    pub fn create<P: AsRef<Path>>(path: P, name: &str, repeat: Option<Repeat>) -> io::Result<VirtualDevice> {
+        let file = try!(OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path));
+        try!(ioctl(&file, UI_SET_EVBIT, EV_KEY));
+        // buttons would make it a mouse or joystick for udev
+        for code in (1..BTN_MISC).chain(KEY_OK..BTN_TRIGGER_HAPPY) {
+            try!(ioctl(&file, UI_SET_KEYBIT, code));
+        }
+        try!(ioctl(&file, UI_SET_EVBIT, EV_LED));
+        for &led in &[LED_NUML, LED_CAPSL, LED_SCROLLL] {
+            try!(ioctl(&file, UI_SET_LEDBIT, led));
+        }
+        if repeat.is_some() {
+            try!(ioctl(&file, UI_SET_EVBIT, EV_REP));
+        }
+        let mut dev: libc::uinput_user_dev = unsafe { mem::zeroed() };
+        for (c, b) in dev.name.iter_mut().zip(name.bytes().take(libc::UINPUT_MAX_NAME_SIZE - 1)) {
+            *c = b as libc::c_char;
+        }
+        dev.id.bustype = BUS_VIRTUAL;
+        try!((&file).write_all(as_bytes(&dev)));
+        try!(ioctl(&file, UI_DEV_CREATE, 0));
+        let mut device = VirtualDevice { file: file };
+        if let Some(repeat) = repeat {
+            // the input core starts with its default of 250 ms and 33 ms, set like any other value
+            try!(device.write(EV_REP, REP_DELAY, millis(repeat.delay)));
+            try!(device.write(EV_REP, REP_PERIOD, millis(repeat.period)));
+            try!(device.write(EV_SYN, SYN_REPORT, 0));
+        }
+        Ok(device)
+    }
+
+    fn write(&mut self, kind: u16, code: u16, value: i32) -> io::Result<()> {
+        let mut event: libc::input_event = unsafe { mem::zeroed() };
+        event.type_ = kind;
+        event.code = code;
+        event.value = value;
+        self.file.write_all(as_bytes(&event))
+    }
+
+    fn send(&mut self, key: &UinputKey, value: i32) -> error::Result<()> {
+        Ok(try!(self.write(key.kind() as u16, key.code() as u16, value)))
+    }
+}
+
+impl KeySink for VirtualDevice {
+    fn press(&mut self, key: &UinputKey) -> error::Result<()> {
+        self.send(key, 1)
+    }
+
+    fn release(&mut self, key: &UinputKey) -> error::Result<()> {
+        self.send(key, 0)
+    }
+
+    fn synchronize(&mut self) -> error::Result<()> {
+        Ok(try!(self.write(EV_SYN, SYN_REPORT, 0)))
+    }
+
+    fn repeat(&mut self, key: &UinputKey) -> error::Result<()> {
+        self.send(key, 2)
+    }
+
+    /// Reads the LED events the system wrote to the device since the last call.
+    fn leds(&mut self) -> error::Result<Vec<(Lock, bool)>> {
+        let mut changes = Vec::new();
+        loop {
+            let mut event: libc::input_event = unsafe { mem::zeroed() };
+            let read = {
+                let buf = unsafe {
+                    slice::from_raw_parts_mut(&mut event as *mut _ as *mut u8, mem::size_of_val(&event))
+                };
+                self.file.read(buf)
+            };
+            match read {
+                Ok(n) if n == mem::size_of_val(&event) => {},
+                Ok(_) => break,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+            if event.type_ != EV_LED {
+                continue;
+            }
+            let lock = match event.code {
+                LED_CAPSL => Lock::Caps,
+                LED_NUML => Lock::Num,
+                LED_SCROLLL => Lock::Scroll,
+                _ => continue,
+            };
+            changes.push((lock, event.value != 0));
+        }
+        Ok(changes)
+    }
+}
+
+impl Drop for VirtualDevice {
+    fn drop(&mut self) {
+        // closing the file would destroy it as well
+        let _ = ioctl(&self.file, UI_DEV_DESTROY, 0);
+    }
+}
+
+fn ioctl(file: &File, request: u64, arg: u16) -> io::Result<()> {
+    if unsafe { libc::ioctl(file.as_raw_fd(), request as _, arg as libc::c_int) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn millis(duration: Duration) -> i32 {
+    (duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64) as i32
+}
+
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+}
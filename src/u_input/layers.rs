@@ -0,0 +1,110 @@
+use libusb::Result as UsbResult;
+use uinput::event::{Keyboard as UinputKey};
+use g910::*;
+
+use backend::Leds;
+use super::UinputHandler;
+
+impl UinputHandler {
+    /// Handles the keys activating layers, returns whether the event was consumed.
+    pub(super) fn handle_layer_key(&mut self, evt: &KeyEvent, keyboard: &mut Leds) -> UsbResult<bool> {
+        match evt {
+            &KeyEvent::KeyPressed(Key::Standard(s)) => {
+                let key = Key::Standard(s);
+                if let Some(name) = self.momentary.get(&s).cloned() {
+                    try!(self.activate_layer(&name, Some(key.clone()), keyboard));
+                } else if let Some(name) = self.toggle.get(&s).cloned() {
+                    try!(self.toggle_layer(&name, keyboard));
+                } else {
+                    return Ok(false);
+                }
+                // releases of the key don't emit anything
+                self.pressed.insert(key, Vec::new());
+                Ok(true)
+            },
+            &KeyEvent::KeyReleased(ref key) => {
+                match self.stack.iter().rposition(|&(_, ref k)| k.as_ref() == Some(key)) {
+                    Some(pos) => {
+                        self.stack.remove(pos);
+                        try!(self.light_layers(keyboard));
+                    },
+                    None => {},
+                }
+                // the release itself is handled as usual
+                Ok(false)
+            },
+            _ => Ok(false),
+        }
+    }
+
+    /// Activates the named layer while the given key is held, or until toggled off if None.
+    pub(super) fn activate_layer(&mut self, name: &str, key: Option<Key>, keyboard: &mut Leds) -> UsbResult<()> {
+        match self.layer_index(name) {
+            Some(index) => {
+                self.stack.push((index, key));
+                self.light_layers(keyboard)
+            },
+            None => Ok(()),
+        }
+    }
+
+    fn toggle_layer(&mut self, name: &str, keyboard: &mut Leds) -> UsbResult<()> {
+        let index = match self.layer_index(name) {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        match self.stack.iter().position(|&(i, ref k)| i == index && k.is_none()) {
+            Some(pos) => {
+                self.stack.remove(pos);
+                self.light_layers(keyboard)
+            },
+            None => self.activate_layer(name, Option::None, keyboard),
+        }
+    }
+
+    /// Returns the index of the named layer. Names are checked when they are set, so it's only
+    /// None if a layer is removed by reloading the config.
+    fn layer_index(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|l| l.name == name)
+    }
+
+    /// Returns the keys bound to the given key on the topmost active layer binding it.
+    pub(super) fn layer_binding(&self, key: StandardKey) -> Option<Vec<UinputKey>> {
+        self.stack.iter().rev()
+            .filter_map(|&(index, _)| self.layers[index].bindings.get(&key))
+            .next()
+            .cloned()
+    }
+
+    /// Lights the bound keys of all active layers and restores the color of the ones which
+    /// aren't anymore.
+    pub(super) fn light_layers(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        let mut colors = Vec::new();
+        for &(index, _) in &self.stack {
+            let layer = &self.layers[index];
+            for key in layer.bindings.keys() {
+                colors.retain(|&(ref k, _): &(Key, Color)| *k != Key::Standard(*key));
+                colors.push((Key::Standard(*key), layer.color));
+            }
+        }
+        let mut key_colors: Vec<_> = self.lit.iter()
+            .filter(|&&(ref key, _)| !colors.iter().any(|&(ref k, _)| k == key))
+            .map(|&(ref key, previous)| KeyColor::new(key.clone(), previous))
+            .collect();
+        let lit = colors.iter()
+            .map(|&(ref k, _)| {
+                // keys lit before keep the color from before their first layer
+                let previous = self.lit.iter().find(|&&(ref l, _)| l == k).map(|&(_, c)| c)
+                    .or_else(|| keyboard.color(k))
+                    .unwrap_or(Color::new(0, 0, 0));
+                (k.clone(), previous)
+            })
+            .collect();
+        self.lit = lit;
+        key_colors.extend(colors.into_iter().map(|(k, c)| KeyColor::new(k, c)));
+        if key_colors.is_empty() {
+            return Ok(());
+        }
+        keyboard.set_key_colors(key_colors)
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use libusb::Result as UsbResult;
+use g910::*;
+
+use backend::Leds;
+
+/// A lock with an LED on the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lock {
+    Caps,
+    Num,
+    Scroll,
+}
+
+impl Lock {
+    /// Returns the key toggling the lock.
+    pub fn key(&self) -> StandardKey {
+        match *self {
+            Lock::Caps => StandardKey::CapsLock,
+            Lock::Num => StandardKey::NumLock,
+            Lock::Scroll => StandardKey::ScrollLock,
+        }
+    }
+}
+
+/// Mirrors the lock LEDs of the virtual device to the lock keys.
+///
+/// The LEDs are read from the device the `UinputHandler` emits to, see `KeySink::leds`. They
+/// are set by the desktop like the ones of any other keyboard, also when the device is created.
+/// Keys are lit on top of the other handlers and get their previous colors back once their
+/// lock is turned off.
+pub struct LockLeds {
+    color: Color,
+    board: Option<Color>,
+    on: Vec<Lock>,
+    /// The colors of the keys lit by this, from before they were lit.
+    previous: HashMap<Key, Color>,
+    /// Whether reading the LEDs failed last time, to report failures only once.
+    failing: bool,
+}
+
+impl LockLeds {
+    pub fn new(color: Color) -> LockLeds {
+        LockLeds {
+            color: color,
+            board: None,
+            on: Vec::new(),
+            previous: HashMap::new(),
+            failing: false,
+        }
+    }
+
+    /// Lights the whole board in the given color while CapsLock is on.
+    pub fn board(mut self, color: Color) -> LockLeds {
+        self.board = Some(color);
+        self
+    }
+
+    /// Returns whether the lock's LED is on.
+    pub fn is_on(&self, lock: Lock) -> bool {
+        self.on.contains(&lock)
+    }
+
+    /// Returns whether a failure to read the LEDs is the first one in a row, which is the only
+    /// one reported.
+    pub(super) fn first_failure(&mut self) -> bool {
+        let first = !self.failing;
+        self.failing = true;
+        first
+    }
+
+    /// Applies the LED changes read from the device and lights the keys accordingly.
+    pub(super) fn update(&mut self, changes: &[(Lock, bool)], keyboard: &mut Leds) -> UsbResult<()> {
+        self.failing = false;
+        let before = self.lit();
+        for &(lock, on) in changes {
+            self.on.retain(|&l| l != lock);
+            if on {
+                self.on.push(lock);
+            }
+        }
+        let lit = self.lit();
+        let mut key_colors = Vec::new();
+        for (key, _) in before {
+            if !lit.iter().any(|&(ref k, _)| *k == key) {
+                let previous = self.previous.remove(&key).unwrap_or(Color::new(0, 0, 0));
+                key_colors.push(KeyColor::new(key, previous));
+            }
+        }
+        for (key, color) in lit {
+            if !self.previous.contains_key(&key) {
+                let previous = keyboard.color(&key).unwrap_or(Color::new(0, 0, 0));
+                self.previous.insert(key.clone(), previous);
+            }
+            key_colors.push(KeyColor::new(key, color));
+        }
+        if key_colors.is_empty() {
+            return Ok(());
+        }
+        keyboard.set_key_colors(key_colors)
+    }
+
+    /// Returns the keys lit for the locks which are on and their colors.
+    fn lit(&self) -> Vec<(Key, Color)> {
+        let mut lit = Vec::new();
+        if let Some(board) = self.board {
+            if self.is_on(Lock::Caps) {
+                lit.extend(Key::values().into_iter()
+                    .filter(|k| match k {
+                        &Key::Media(_) => false,
+                        _ => true,
+                    })
+                    .map(|k| (k, board)));
+            }
+        }
+        for lock in &self.on {
+            let key = Key::Standard(lock.key());
+            lit.retain(|&(ref k, _)| *k != key);
+            lit.push((key, self.color));
+        }
+        lit
+    }
+}
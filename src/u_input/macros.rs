@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+use g910::*;
+
+/// A key press or release of a recorded macro.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MacroEvent {
+    pub pressed: bool,
+    pub key: Key,
+    /// The time since the previous event of the macro, or since the start of the recording.
+    pub delay: Duration,
+}
+
+/// The macros recorded on the G-keys.
+#[derive(Debug, Clone)]
+pub struct Macros {
+    macros: HashMap<GamingKey, Vec<MacroEvent>>,
+}
+
+impl Macros {
+    pub fn new() -> Macros {
+        Macros {
+            macros: HashMap::new(),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Macros> {
+        let file = try!(File::open(path));
+        Macros::read_from(BufReader::new(file))
+    }
+
+    /// Reads macros written by `write_to`, skipping invalid lines.
+    pub fn read_from<R: BufRead>(reader: R) -> io::Result<Macros> {
+        let mut macros = Macros::new();
+        let names: HashMap<String, Key> = Key::values().into_iter()
+            .map(|k| (format!("{:?}", k), k))
+            .collect();
+        for line in reader.lines() {
+            let line = try!(line);
+            let split: Vec<_> = line.split_whitespace().collect();
+            if split.len() != 4 {
+                continue;
+            }
+            let gaming = match names.get(split[0]) {
+                Some(&Key::Gaming(g)) => g,
+                _ => continue,
+            };
+            let pressed = match split[1] {
+                "press" => true,
+                "release" => false,
+                _ => continue,
+            };
+            let key = match names.get(split[2]) {
+                Some(key) => key.clone(),
+                None => continue,
+            };
+            let delay = match split[3].parse::<u64>() {
+                Ok(ms) => Duration::from_millis(ms),
+                Err(_) => continue,
+            };
+            macros.macros.entry(gaming).or_insert_with(Vec::new).push(MacroEvent {
+                pressed: pressed,
+                key: key,
+                delay: delay,
+            });
+        }
+        Ok(macros)
+    }
+
+    /// Writes all macros to the given file, like `Heatmap::save` via a temporary file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        {
+            let mut writer = BufWriter::new(try!(File::create(&tmp)));
+            try!(self.write_to(&mut writer));
+            try!(writer.flush());
+        }
+        fs::rename(&tmp, path)
+    }
+
+    /// Writes one `<G-key> <press|release> <key> <delay in ms>` line per event.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut keys: Vec<_> = self.macros.keys().collect();
+        keys.sort_by_key(|k| format!("{:?}", Key::Gaming(**k)));
+        for gaming in keys {
+            for evt in &self.macros[gaming] {
+                let ms = evt.delay.as_secs() * 1000 + (evt.delay.subsec_nanos() / 1_000_000) as u64;
+                try!(writeln!(writer, "{:?} {} {:?} {}", Key::Gaming(*gaming),
+                    if evt.pressed { "press" } else { "release" }, evt.key, ms));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: GamingKey) -> Option<&[MacroEvent]> {
+        self.macros.get(&key).map(|events| &events[..])
+    }
+
+    /// Sets the macro of the given key, an empty one removes it.
+    pub fn set(&mut self, key: GamingKey, events: Vec<MacroEvent>) {
+        if events.is_empty() {
+            self.macros.remove(&key);
+        } else {
+            self.macros.insert(key, events);
+        }
+    }
+}
@@ -0,0 +1,1358 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use g910::*;
+use uinput::Device;
+use uinput::event::{Keyboard as UinputKey};
+use uinput::event::keyboard::Misc;
+use libusb;
+use libusb::Result as UsbResult;
+
+use backend::{self, KeyboardHandler, Leds};
+use config::{self, Config, DualRole, Hold, Layer, Repeat};
+use error;
+use layout::Layout;
+
+pub use self::device::VirtualDevice;
+pub use self::locks::{Lock, LockLeds};
+pub use self::macros::{MacroEvent, Macros};
+
+mod device;
+mod layers;
+mod locks;
+mod macros;
+
+/// Receiver of the key events a `UinputHandler` emits.
+///
+/// Implemented by `VirtualDevice`, by `uinput::Device` and by `mock::MockSink`, which records
+/// the events instead.
+pub trait KeySink {
+    fn press(&mut self, key: &UinputKey) -> error::Result<()>;
+    fn release(&mut self, key: &UinputKey) -> error::Result<()>;
+    fn synchronize(&mut self) -> error::Result<()>;
+
+    /// Emits an auto-repeat of a held key, an event with value 2. Pressing it again instead
+    /// wouldn't do, the input core drops presses of keys which are already down.
+    fn repeat(&mut self, key: &UinputKey) -> error::Result<()>;
+
+    /// Returns the changes of the lock LEDs since the last call, in order.
+    ///
+    /// Fails by default, as only sinks with LEDs can tell.
+    fn leds(&mut self) -> error::Result<Vec<(Lock, bool)>> {
+        Err(error::Error::Io(io::Error::new(io::ErrorKind::Other, "the sink has no LEDs")))
+    }
+}
+
+/// A device created with the uinput crate, which doesn't have LEDs.
+impl KeySink for Device {
+    fn press(&mut self, key: &UinputKey) -> error::Result<()> {
+        Ok(try!(Device::press(self, key)))
+    }
+
+    fn release(&mut self, key: &UinputKey) -> error::Result<()> {
+        Ok(try!(Device::release(self, key)))
+    }
+
+    fn synchronize(&mut self) -> error::Result<()> {
+        Ok(try!(Device::synchronize(self)))
+    }
+
+    fn repeat(&mut self, key: &UinputKey) -> error::Result<()> {
+        Ok(try!(Device::send(self, *key, 2)))
+    }
+}
+
+/// Builder for a `UinputHandler` with a custom device path or name.
+pub struct UinputBuilder {
+    path: PathBuf,
+    name: String,
+    layout: Layout,
+    config: Option<PathBuf>,
+    macros: Option<PathBuf>,
+    macro_timing: bool,
+    repeat: Option<Repeat>,
+}
+
+impl UinputBuilder {
+    /// Creates a builder for a device called `logitech-g910-rs` created via `/dev/uinput`.
+    pub fn new() -> UinputBuilder {
+        UinputBuilder {
+            path: PathBuf::from("/dev/uinput"),
+            name: "logitech-g910-rs".to_string(),
+            layout: Layout::DeQwertz,
+            config: None,
+            macros: None,
+            macro_timing: false,
+            repeat: None,
+        }
+    }
+
+    /// Sets the path of the uinput device node.
+    pub fn path<P: Into<PathBuf>>(mut self, path: P) -> UinputBuilder {
+        self.path = path.into();
+        self
+    }
+
+    /// Sets the name of the virtual device.
+    pub fn name<S: Into<String>>(mut self, name: S) -> UinputBuilder {
+        self.name = name.into();
+        self
+    }
+
+    /// Sets the layout of the keyboard, defaults to `Layout::DeQwertz`.
+    pub fn layout(mut self, layout: Layout) -> UinputBuilder {
+        self.layout = layout;
+        self
+    }
+
+    /// Sets the config file keys are remapped by, see `UinputHandler::config`.
+    pub fn config<P: Into<PathBuf>>(mut self, path: P) -> UinputBuilder {
+        self.config = Some(path.into());
+        self
+    }
+
+    /// Enables macro recording, persisting the macros to the given file, see
+    /// `UinputHandler::macros`.
+    pub fn macros<P: Into<PathBuf>>(mut self, path: P) -> UinputBuilder {
+        self.macros = Some(path.into());
+        self
+    }
+
+    /// Sets whether macros are played back with their original timing, see
+    /// `UinputHandler::macro_timing`.
+    pub fn macro_timing(mut self, timing: bool) -> UinputBuilder {
+        self.macro_timing = timing;
+        self
+    }
+
+    /// Lets the device repeat held keys with the given delay and rate in repeats per second, see
+    /// `VirtualDevice::create`. Off by default.
+    ///
+    /// The input core then repeats every key, don't combine it with `UinputHandler::repeat`,
+    /// which is needed for repeats of single keys.
+    pub fn repeat(mut self, delay: Duration, rate: u32) -> UinputBuilder {
+        self.repeat = Some(Repeat::new(delay, rate));
+        self
+    }
+
+    /// Creates only the virtual device, e.g. for other handlers emitting keys like
+    /// `ChordHandler`.
+    pub fn create_device(&self) -> error::Result<VirtualDevice> {
+        VirtualDevice::create(&self.path, &self.name, self.repeat).map_err(error::Error::Device)
+    }
+
+    /// Creates the virtual device and a handler emitting to it.
+    ///
+    /// Fails if the uinput device node can't be opened, e.g. due to missing permissions, or if
+    /// the config or macro file can't be loaded.
+    pub fn build(self) -> error::Result<UinputHandler> {
+        let device = try!(self.create_device());
+        let mut handler = UinputHandler::with_sink(device)
+            .layout(self.layout)
+            .macro_timing(self.macro_timing);
+        if let Some(path) = self.config {
+            handler = try!(handler.config(path));
+        }
+        if let Some(path) = self.macros {
+            handler = try!(handler.macros(path));
+        }
+        Ok(handler)
+    }
+}
+
+/// How often the config file is checked for changes.
+const RELOAD_INTERVAL: u64 = 1;
+/// Milliseconds between two checks for due macro events and dual-role keys held long enough.
+const TICK_INTERVAL: u64 = 10;
+/// Milliseconds after which a dual-role key counts as held by default.
+const TAPPING_TERM: u64 = 200;
+
+struct ConfigFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+enum Recording {
+    Idle,
+    /// MR has been pressed, waiting for the G-key to record.
+    Armed,
+    Recording {
+        key: GamingKey,
+        events: Vec<MacroEvent>,
+        last: Instant,
+        /// The color of the G-key before it has been lit.
+        color: Color,
+    },
+}
+
+/// A dual-role key which has been pressed, but not yet decided whether it is tapped or held.
+struct Pending {
+    key: StandardKey,
+    since: Instant,
+    /// Events of other keys which happened in the meantime, processed once it is decided.
+    buffered: Vec<(bool, Key)>,
+}
+
+pub struct UinputHandler {
+    device: Box<KeySink>,
+    layout: Layout,
+    remap: HashMap<StandardKey, StandardKey>,
+    gaming: HashMap<GamingKey, Vec<UinputKey>>,
+    config: Option<ConfigFile>,
+    /// The emitted keys of every pressed key, to release the same keys even if the mapping
+    /// changed in between.
+    pressed: HashMap<Key, Vec<UinputKey>>,
+    /// All keys pressed on the device, to release them when shutting down.
+    held: Vec<UinputKey>,
+    /// Whether events have been written to the device since the last synchronization.
+    unsynced: bool,
+    last_reload: Option<Instant>,
+    macros: Option<Macros>,
+    macro_path: Option<PathBuf>,
+    macro_timing: bool,
+    recording: Recording,
+    /// Events of macros being played back with timing and when they are due.
+    playback: VecDeque<(Instant, bool, Key)>,
+    dual: HashMap<StandardKey, DualRole>,
+    tapping_term: Duration,
+    pending: Option<Pending>,
+    layers: Vec<Layer>,
+    momentary: HashMap<StandardKey, String>,
+    toggle: HashMap<StandardKey, String>,
+    /// The active layers by index into `layers`, the last one taking precedence, with the key
+    /// holding it or None if it's toggled on.
+    stack: Vec<(usize, Option<Key>)>,
+    /// The keys lit to show the active layers with the color they had before.
+    lit: Vec<(Key, Color)>,
+    repeat: Option<Repeat>,
+    key_repeat: HashMap<StandardKey, Option<Repeat>>,
+    /// The key being repeated, when it's repeated next and its repeat.
+    repeating: Option<(Key, Instant, Repeat)>,
+    locks: Option<LockLeds>,
+    on_error: Option<Box<FnMut(error::Error)>>,
+}
+
+impl UinputHandler {
+    /// Creates a UinputHandler with a default virtual device.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the virtual device can't be created, see `try_new`.
+    pub fn new() -> UinputHandler {
+        UinputHandler::try_new().unwrap()
+    }
+
+    /// Creates a UinputHandler with a default virtual device, use `UinputBuilder` to configure
+    /// it.
+    pub fn try_new() -> error::Result<UinputHandler> {
+        UinputBuilder::new().build()
+    }
+
+    /// Creates a UinputHandler emitting its events to the given sink instead of a new virtual
+    /// device.
+    pub fn with_sink<S: KeySink + 'static>(sink: S) -> UinputHandler {
+        UinputHandler {
+            device: Box::new(sink),
+            layout: Layout::DeQwertz,
+            remap: HashMap::new(),
+            gaming: config::default_gaming(),
+            config: None,
+            pressed: HashMap::new(),
+            held: Vec::new(),
+            unsynced: false,
+            last_reload: None,
+            macros: None,
+            macro_path: None,
+            macro_timing: false,
+            recording: Recording::Idle,
+            playback: VecDeque::new(),
+            dual: HashMap::new(),
+            tapping_term: Duration::from_millis(TAPPING_TERM),
+            pending: None,
+            layers: Vec::new(),
+            momentary: HashMap::new(),
+            toggle: HashMap::new(),
+            stack: Vec::new(),
+            lit: Vec::new(),
+            repeat: None,
+            key_repeat: HashMap::new(),
+            repeating: None,
+            locks: None,
+            on_error: None,
+        }
+    }
+
+    /// Sets the layout of the keyboard, defaults to `Layout::DeQwertz`.
+    pub fn layout(mut self, layout: Layout) -> UinputHandler {
+        self.layout = layout;
+        self
+    }
+
+    /// Emits `to` whenever `from` is pressed.
+    pub fn remap(mut self, from: StandardKey, to: StandardKey) -> UinputHandler {
+        self.remap.insert(from, to);
+        self
+    }
+
+    /// Emits the given keys, pressed in order and released in reverse, whenever the G- or M-key
+    /// is pressed. An empty chord disables the key.
+    ///
+    /// Defaults to the keys of `config::default_gaming`. The virtual device supports all keys,
+    /// including `F13` to `F24` and `Macro`.
+    pub fn gaming(mut self, key: GamingKey, emit: Vec<UinputKey>) -> UinputHandler {
+        self.gaming.insert(key, emit);
+        self
+    }
+
+    /// Makes the key emit `tap` when tapped and `hold` when held, e.g. Esc and LeftControl for
+    /// CapsLock.
+    ///
+    /// The key counts as held once it's been pressed for longer than the tapping term or when
+    /// another key is pressed and released while it's down. Events of other keys are delayed
+    /// until it's decided, so they are emitted in order with the right modifiers.
+    pub fn dual_role(mut self, key: StandardKey, tap: UinputKey, hold: UinputKey) -> UinputHandler {
+        self.dual.insert(key, DualRole { tap: tap, hold: Hold::Key(hold) });
+        self
+    }
+
+    /// Makes the key emit `tap` when tapped and activate the named layer while held, like
+    /// `dual_role`.
+    ///
+    /// Fails if the layer hasn't been added with `layer` before.
+    pub fn dual_layer<S: Into<String>>(mut self, key: StandardKey, tap: UinputKey, layer: S) -> error::Result<UinputHandler> {
+        let layer = try!(self.known_layer(layer.into()));
+        self.dual.insert(key, DualRole { tap: tap, hold: Hold::Layer(layer) });
+        Ok(self)
+    }
+
+    /// Sets the time after which a dual-role key counts as held, defaults to 200ms.
+    pub fn tapping_term(mut self, term: Duration) -> UinputHandler {
+        self.tapping_term = term;
+        self
+    }
+
+    /// Adds a layer, which is activated by keys set with `momentary`, `toggle` or `dual_layer`.
+    ///
+    /// While a layer is active, its bindings take precedence over the remapping and the bindings
+    /// of layers activated before. Its bound keys are lit in its color and get their previous
+    /// color back when it's deactivated.
+    pub fn layer(mut self, layer: Layer) -> UinputHandler {
+        self.layers.push(layer);
+        self
+    }
+
+    /// Activates the named layer while the key is held. The key itself isn't emitted.
+    ///
+    /// Fails if the layer hasn't been added with `layer` before.
+    pub fn momentary<S: Into<String>>(mut self, key: StandardKey, layer: S) -> error::Result<UinputHandler> {
+        let layer = try!(self.known_layer(layer.into()));
+        self.momentary.insert(key, layer);
+        Ok(self)
+    }
+
+    /// Turns the named layer on and off with the key. The key itself isn't emitted.
+    ///
+    /// Fails if the layer hasn't been added with `layer` before.
+    pub fn toggle<S: Into<String>>(mut self, key: StandardKey, layer: S) -> error::Result<UinputHandler> {
+        let layer = try!(self.known_layer(layer.into()));
+        self.toggle.insert(key, layer);
+        Ok(self)
+    }
+
+    fn known_layer(&self, name: String) -> error::Result<String> {
+        if self.layers.iter().any(|l| l.name == name) {
+            Ok(name)
+        } else {
+            Err(error::Error::Config(format!("unknown layer {:?}", name)))
+        }
+    }
+
+    /// Repeats held keys with the given delay and rate in repeats per second. Off by default,
+    /// leaving repeating to the desktop.
+    ///
+    /// Like the kernel does, only the last pressed key is repeated, until it's released or
+    /// another key is pressed. The repeats are emitted by the handler as events with value 2,
+    /// which sessions using libinput ignore, see `VirtualDevice::create`. Use it with a device
+    /// which doesn't repeat keys itself, i.e. without `UinputBuilder::repeat`.
+    pub fn repeat(mut self, delay: Duration, rate: u32) -> UinputHandler {
+        self.repeat = Some(Repeat::new(delay, rate));
+        self
+    }
+
+    /// Sets the repeat of a single key, None disables repeating it.
+    pub fn key_repeat(mut self, key: StandardKey, repeat: Option<(Duration, u32)>) -> UinputHandler {
+        self.key_repeat.insert(key, repeat.map(|(delay, rate)| Repeat::new(delay, rate)));
+        self
+    }
+
+    /// Lights CapsLock, NumLock and ScrollLock while the LEDs of the device are on, see
+    /// `LockLeds`. Failing to read them, e.g. with a sink without LEDs, is passed to `on_error`.
+    pub fn lock_leds(mut self, locks: LockLeds) -> UinputHandler {
+        self.locks = Some(locks);
+        self
+    }
+
+    /// Calls the function with errors which don't stop the handler, e.g. an invalid config file
+    /// when reloading or macros which can't be saved, and with the cause of failed writes to
+    /// the device. They are ignored by default.
+    pub fn on_error<F: FnMut(error::Error) + 'static>(mut self, f: F) -> UinputHandler {
+        self.on_error = Some(Box::new(f));
+        self
+    }
+
+    /// Loads the remapping from the given config file, see `Config` for its format.
+    ///
+    /// The file is checked for changes every second and reloaded. If the changed file is
+    /// invalid, the error is passed to `on_error` and the previous configuration is kept.
+    ///
+    /// A reload can turn on dual-role keys or auto-repeat, which need `handle_time`, only
+    /// because setting a config file makes the handler tick. This has to be done before the
+    /// handler is turned into a `Handler`, which fixes the interval once.
+    pub fn config<P: Into<PathBuf>>(mut self, path: P) -> error::Result<UinputHandler> {
+        let path = path.into();
+        let modified = modified(&path);
+        let config = try!(Config::load(&path, self.layout));
+        self.apply(config);
+        self.config = Some(ConfigFile {
+            path: path,
+            modified: modified,
+        });
+        Ok(self)
+    }
+
+    /// Enables recording macros on the G-keys, which are persisted to the given file.
+    ///
+    /// To record a macro, press MR, then the G-key to record (which is lit red while recording),
+    /// type the macro and press MR again. Pressing MR twice cancels, recording nothing removes
+    /// the macro of the key. From then on, pressing the G-key plays back the macro instead of
+    /// emitting the key's chord. With macros enabled MR only records and doesn't emit anything.
+    ///
+    /// The file is created on the first recording, but fails if it exists and can't be read.
+    pub fn macros<P: Into<PathBuf>>(mut self, path: P) -> error::Result<UinputHandler> {
+        let path = path.into();
+        let macros = match Macros::load(&path) {
+            Ok(macros) => macros,
+            // nothing has been recorded yet
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Macros::new(),
+            Err(e) => return Err(e.into()),
+        };
+        self.macros = Some(macros);
+        self.macro_path = Some(path);
+        Ok(self)
+    }
+
+    /// Sets whether macros are played back with the delays they were recorded with, instead
+    /// of all at once. Defaults to false.
+    pub fn macro_timing(mut self, timing: bool) -> UinputHandler {
+        self.macro_timing = timing;
+        self
+    }
+
+    /// Returns the recorded macros, if recording is enabled.
+    pub fn recorded_macros(&self) -> Option<&Macros> {
+        self.macros.as_ref()
+    }
+
+    /// Releases all keys pressed on the device, including the ones of macros being played back
+    /// and dual-role keys not decided yet.
+    ///
+    /// This happens automatically when the handler is dropped, e.g. when it's removed from the
+    /// keyboard or the program exits, when a USB error occurs while handling an event and when
+    /// a `ProfileManager` switches away from its profile.
+    pub fn release_all(&mut self) -> error::Result<()> {
+        self.pressed.clear();
+        self.playback.clear();
+        self.repeating = None;
+        self.pending = None;
+        self.stack.clear();
+        // whoever draws on the keyboard next replaces the layer colors, don't restore them later
+        self.lit.clear();
+        let held = ::std::mem::replace(&mut self.held, Vec::new());
+        for key in held.iter().rev() {
+            try!(self.device.release(key));
+        }
+        self.unsynced = false;
+        try!(self.device.synchronize());
+        Ok(())
+    }
+
+    fn press(&mut self, key: &UinputKey) -> UsbResult<()> {
+        self.unsynced = true;
+        if let Err(e) = self.device.press(key) {
+            return Err(self.device_error(e));
+        }
+        if !self.held.contains(key) {
+            self.held.push(*key);
+        }
+        Ok(())
+    }
+
+    fn release(&mut self, key: &UinputKey) -> UsbResult<()> {
+        self.held.retain(|k| k != key);
+        self.unsynced = true;
+        match self.device.release(key) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(self.device_error(e)),
+        }
+    }
+
+    /// Ends the frame of the events written since the last call, if there are any.
+    fn synchronize(&mut self) -> UsbResult<()> {
+        if !self.unsynced {
+            return Ok(());
+        }
+        self.unsynced = false;
+        match self.device.synchronize() {
+            Ok(()) => Ok(()),
+            Err(e) => Err(self.device_error(e)),
+        }
+    }
+
+    fn report(&mut self, err: error::Error) {
+        if let Some(ref mut on_error) = self.on_error {
+            on_error(err);
+        }
+    }
+
+    /// Reports the cause of a failed write and returns the error to return from the handler.
+    fn device_error(&mut self, err: error::Error) -> libusb::Error {
+        self.report(err);
+        libusb::Error::Io
+    }
+
+    /// Releases all keys if the result is an error, as the keyboard might be gone. The result
+    /// is returned as is, failing to release is passed to `on_error`.
+    fn release_on_error(&mut self, result: UsbResult<()>) -> UsbResult<()> {
+        if result.is_err() {
+            if let Err(e) = self.release_all() {
+                self.report(e);
+            }
+        }
+        result
+    }
+
+    fn apply(&mut self, config: Config) {
+        if let Some(layout) = config.layout {
+            self.layout = layout;
+        }
+        self.remap = config.remap;
+        self.gaming = config::default_gaming();
+        self.gaming.extend(config.gaming);
+        if let Some(term) = config.tapping_term {
+            self.tapping_term = term;
+        }
+        self.dual = config.dual;
+        self.layers = config.layers;
+        self.momentary = config.momentary;
+        self.toggle = config.toggle;
+        // indices into the old layers are meaningless now, the keys lit for them are restored
+        // by the `light_layers` following every reload
+        self.stack.clear();
+        self.repeat = config.repeat;
+        self.key_repeat = config.key_repeat;
+        self.repeating = None;
+    }
+
+    /// Reloads the config file if it changed, returns whether it has been reloaded.
+    fn reload(&mut self) -> bool {
+        let (path, modified) = match self.config {
+            Some(ref mut file) => {
+                let modified = modified(&file.path);
+                if modified == file.modified {
+                    return false;
+                }
+                file.modified = modified;
+                (file.path.clone(), modified)
+            },
+            None => return false,
+        };
+        if modified.is_none() {
+            self.report(error::Error::Config(format!("{} vanished, keeping the current config",
+                path.display())));
+            return false;
+        }
+        match Config::load(&path, self.layout) {
+            Ok(config) => {
+                self.apply(config);
+                true
+            },
+            Err(e) => {
+                self.report(e);
+                false
+            },
+        }
+    }
+
+    fn map_key(&self, key: &Key) -> Vec<UinputKey> {
+        match key {
+            &Key::Standard(s) => match self.layer_binding(s) {
+                Some(keys) => keys,
+                None => self.layout.to_uinput_key(*self.remap.get(&s).unwrap_or(&s))
+                    .into_iter().collect(),
+            },
+            &Key::Media(m) => m.to_uinput_key().into_iter().collect(),
+            &Key::Gaming(g) => self.gaming.get(&g).cloned().unwrap_or_default(),
+            &Key::Logo(_) => Vec::new(),
+        }
+    }
+}
+
+impl UinputHandler {
+    /// Handles a key event, holding it back while a dual-role key is undecided.
+    fn dispatch(&mut self, pressed: bool, key: Key, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        if let Some(dual) = self.pending.as_ref().map(|p| p.key) {
+            if !pressed && key == Key::Standard(dual) {
+                // released within the tapping term
+                let pending = self.pending.take().unwrap();
+                try!(self.tap(dual));
+                return self.replay(pending.buffered, now, keyboard);
+            }
+            let interrupted = !pressed && self.pending.as_ref().unwrap().buffered.iter()
+                .any(|&(pressed, ref k)| pressed && *k == key);
+            if pressed {
+                self.pending.as_mut().unwrap().buffered.push((pressed, key));
+                return Ok(());
+            }
+            if interrupted {
+                // another key has been pressed and released while the dual-role key is down
+                try!(self.hold(now, keyboard));
+                // replaying may have made the released key pending itself, e.g. Space of
+                // CapsLock+Space with both being dual-role keys, which is then tapped
+                return self.dispatch(pressed, key, now, keyboard);
+            }
+            // keys pressed before the dual-role key can be released right away
+        }
+        if let Key::Standard(s) = key {
+            if pressed && self.dual.contains_key(&s) {
+                self.pending = Some(Pending {
+                    key: s,
+                    since: now,
+                    buffered: Vec::new(),
+                });
+                return Ok(());
+            }
+        }
+        let evt = if pressed { KeyEvent::KeyPressed(key) } else { KeyEvent::KeyReleased(key) };
+        self.process(&evt, now, keyboard)
+    }
+
+    /// Decides the pending dual-role key to be held.
+    fn hold(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        let pending = self.pending.take().unwrap();
+        let key = Key::Standard(pending.key);
+        match self.dual.get(&pending.key).map(|role| role.hold.clone()) {
+            Some(Hold::Key(hold)) => {
+                try!(self.press(&hold));
+                try!(self.synchronize());
+                self.pressed.insert(key, vec![hold]);
+            },
+            Some(Hold::Layer(name)) => {
+                try!(self.activate_layer(&name, Some(key.clone()), keyboard));
+                self.pressed.insert(key, Vec::new());
+            },
+            None => {},
+        }
+        self.replay(pending.buffered, now, keyboard)
+    }
+
+    fn tap(&mut self, key: StandardKey) -> UsbResult<()> {
+        if let Some(role) = self.dual.get(&key).cloned() {
+            try!(self.press(&role.tap));
+            try!(self.synchronize());
+            try!(self.release(&role.tap));
+            try!(self.synchronize());
+        }
+        Ok(())
+    }
+
+    fn replay(&mut self, events: Vec<(bool, Key)>, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        for (pressed, key) in events {
+            try!(self.dispatch(pressed, key, now, keyboard));
+        }
+        Ok(())
+    }
+
+    /// Emits a key event in a frame of its own, or handles it as part of a macro.
+    fn process(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        if try!(self.handle_macro_key(evt, now, keyboard)) {
+            return Ok(());
+        }
+        if try!(self.handle_layer_key(evt, keyboard)) {
+            return Ok(());
+        }
+        self.record(evt, now);
+        match evt {
+            &KeyEvent::KeyPressed(ref k) => {
+                let keys = self.map_key(k);
+                for key in &keys {
+                    try!(self.press(key));
+                }
+                self.pressed.insert(k.clone(), keys);
+                self.repeating = self.repeat_of(k).map(|repeat| (k.clone(), now + repeat.delay, repeat));
+            },
+            &KeyEvent::KeyReleased(ref k) => {
+                if self.repeating.as_ref().map_or(false, |&(ref key, _, _)| key == k) {
+                    self.repeating = None;
+                }
+                for key in self.pressed.remove(k).unwrap_or_default().iter().rev() {
+                    try!(self.release(key));
+                }
+            },
+        };
+        self.synchronize()
+    }
+
+    fn repeat_of(&self, key: &Key) -> Option<Repeat> {
+        match key {
+            &Key::Standard(s) => self.key_repeat.get(&s).cloned().unwrap_or(self.repeat),
+            _ => self.repeat,
+        }
+    }
+
+    /// Emits the repeats of the repeated key which are due.
+    fn repeat_due(&mut self, now: Instant) -> UsbResult<()> {
+        let (key, next, repeat) = match self.repeating {
+            Some((ref key, next, repeat)) if next <= now => (key.clone(), next, repeat),
+            _ => return Ok(()),
+        };
+        for k in self.pressed.get(&key).cloned().unwrap_or_default() {
+            self.unsynced = true;
+            if let Err(e) = self.device.repeat(&k) {
+                return Err(self.device_error(e));
+            }
+        }
+        // skip repeats missed in between instead of emitting them in a burst
+        let mut next = next + repeat.period;
+        if next <= now {
+            next = now + repeat.period;
+        }
+        self.repeating = Some((key, next, repeat));
+        self.synchronize()
+    }
+
+    /// Handles MR and G-keys if macros are enabled, returns whether the event was consumed.
+    fn handle_macro_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<bool> {
+        let gaming = match evt {
+            &KeyEvent::KeyPressed(Key::Gaming(g)) => g,
+            // releases of consumed keys are ignored as they weren't added to `pressed`
+            _ => return Ok(false),
+        };
+        if self.macros.is_none() {
+            return Ok(false);
+        }
+        let recording = ::std::mem::replace(&mut self.recording, Recording::Idle);
+        match (recording, gaming) {
+            (Recording::Idle, GamingKey::MR) => self.recording = Recording::Armed,
+            (Recording::Armed, GamingKey::MR) => {},
+            (Recording::Recording { key, events, color, .. }, GamingKey::MR) => {
+                try!(keyboard.set_key_colors(vec![KeyColor::new(Key::Gaming(key), color)]));
+                self.finish_recording(key, events);
+            },
+            (Recording::Armed, key) if is_g_key(key) => {
+                let color = keyboard.color(&Key::Gaming(key)).unwrap_or(Color::new(0, 0, 0));
+                try!(keyboard.set_key_colors(vec![KeyColor::new(Key::Gaming(key), Color::new(255, 0, 0))]));
+                self.recording = Recording::Recording {
+                    key: key,
+                    events: Vec::new(),
+                    last: now,
+                    color: color,
+                };
+            },
+            (Recording::Idle, key) => {
+                let events = match self.macros.as_ref().and_then(|m| m.get(key)) {
+                    Some(events) => events.to_vec(),
+                    None => return Ok(false),
+                };
+                try!(self.play(events, now));
+            },
+            (recording, _) => {
+                self.recording = recording;
+                return Ok(false);
+            },
+        }
+        Ok(true)
+    }
+
+    fn record(&mut self, evt: &KeyEvent, now: Instant) {
+        if let Recording::Recording { ref mut events, ref mut last, .. } = self.recording {
+            let (pressed, key) = match evt {
+                &KeyEvent::KeyPressed(ref k) => (true, k),
+                &KeyEvent::KeyReleased(ref k) => (false, k),
+            };
+            if let &Key::Gaming(_) = key {
+                return;
+            }
+            // keys held since before the recording would be released without being pressed
+            if !pressed && !events.iter().any(|e| e.pressed && e.key == *key) {
+                return;
+            }
+            events.push(MacroEvent {
+                pressed: pressed,
+                key: key.clone(),
+                delay: now.duration_since(*last),
+            });
+            *last = now;
+        }
+    }
+
+    fn finish_recording(&mut self, key: GamingKey, mut events: Vec<MacroEvent>) {
+        // release keys still held, so playing back never leaves keys pressed
+        let mut held = HashSet::new();
+        for evt in &events {
+            if evt.pressed {
+                held.insert(evt.key.clone());
+            } else {
+                held.remove(&evt.key);
+            }
+        }
+        for key in held {
+            events.push(MacroEvent {
+                pressed: false,
+                key: key,
+                delay: Duration::from_secs(0),
+            });
+        }
+        let saved = {
+            let macros = self.macros.as_mut().unwrap();
+            macros.set(key, events);
+            match self.macro_path {
+                Some(ref path) => macros.save(path),
+                None => Ok(()),
+            }
+        };
+        if let Err(e) = saved {
+            self.report(e.into());
+        }
+    }
+
+    fn play(&mut self, events: Vec<MacroEvent>, now: Instant) -> UsbResult<()> {
+        if !self.macro_timing {
+            for evt in events {
+                try!(self.emit(evt.pressed, &evt.key));
+            }
+            return Ok(());
+        }
+        let mut due = self.playback.back().map(|&(due, _, _)| due).unwrap_or(now);
+        for evt in events {
+            due += evt.delay;
+            self.playback.push_back((due, evt.pressed, evt.key));
+        }
+        self.play_due(now)
+    }
+
+    fn play_due(&mut self, now: Instant) -> UsbResult<()> {
+        while self.playback.front().map_or(false, |&(due, _, _)| due <= now) {
+            let (_, pressed, key) = self.playback.pop_front().unwrap();
+            try!(self.emit(pressed, &key));
+        }
+        Ok(())
+    }
+
+    /// Emits the press or release of a key of a macro in a frame of its own, like it's emitted
+    /// when typed.
+    fn emit(&mut self, pressed: bool, key: &Key) -> UsbResult<()> {
+        let keys = self.map_key(key);
+        if pressed {
+            for key in &keys {
+                try!(self.press(key));
+            }
+        } else {
+            for key in keys.iter().rev() {
+                try!(self.release(key));
+            }
+        }
+        self.synchronize()
+    }
+}
+
+fn is_g_key(key: GamingKey) -> bool {
+    use g910::GamingKey::*;
+    match key {
+        G1 | G2 | G3 | G4 | G5 | G6 | G7 | G8 | G9 => true,
+        _ => false,
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl KeyboardHandler for UinputHandler {
+    fn accept_key(&self, evt: &KeyEvent) -> bool {
+        let k = match evt {
+            &KeyEvent::KeyPressed(ref k) => k,
+            &KeyEvent::KeyReleased(ref k) => k,
+        };
+        match k {
+            &Key::Standard(_) => true,
+            &Key::Media(_) => true,
+            &Key::Gaming(_) => true,
+            _ => false
+        }
+    }
+
+    fn handle_key(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        let result = self.handle_event(evt, now, keyboard);
+        self.release_on_error(result)
+    }
+
+    /// Ticks often enough to play back macros and decide dual-role keys in time, which can be
+    /// configured through a reloaded config file as well.
+    fn interval(&self) -> Option<Duration> {
+        let timed = (self.macros.is_some() && self.macro_timing) || !self.dual.is_empty()
+            || self.repeat.is_some() || !self.key_repeat.is_empty() || self.locks.is_some();
+        if timed || self.config.is_some() {
+            Some(Duration::from_millis(TICK_INTERVAL))
+        } else {
+            None
+        }
+    }
+
+    fn handle_time(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        let result = self.tick(now, keyboard);
+        self.release_on_error(result)
+    }
+
+    /// Releases all keys, see `release_all`.
+    fn suspend(&mut self) -> UsbResult<()> {
+        match self.release_all() {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                self.report(e);
+                Err(libusb::Error::Io)
+            },
+        }
+    }
+}
+
+impl UinputHandler {
+    fn handle_event(&mut self, evt: &KeyEvent, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        match evt {
+            &KeyEvent::KeyPressed(ref k) => try!(self.dispatch(true, k.clone(), now, keyboard)),
+            &KeyEvent::KeyReleased(ref k) => try!(self.dispatch(false, k.clone(), now, keyboard)),
+        }
+        self.synchronize()
+    }
+
+    /// Lights the lock keys if their LEDs changed, reporting only the first of consecutive
+    /// failures to read them.
+    fn update_locks(&mut self, keyboard: &mut Leds) -> UsbResult<()> {
+        if self.locks.is_none() {
+            return Ok(());
+        }
+        match self.device.leds() {
+            Ok(changes) => self.locks.as_mut().unwrap().update(&changes, keyboard),
+            Err(e) => {
+                if self.locks.as_mut().unwrap().first_failure() {
+                    self.report(e);
+                }
+                Ok(())
+            },
+        }
+    }
+
+    fn tick(&mut self, now: Instant, keyboard: &mut Leds) -> UsbResult<()> {
+        try!(self.play_due(now));
+        try!(self.repeat_due(now));
+        try!(self.update_locks(keyboard));
+        let held = self.pending.as_ref().map_or(false, |p| now.duration_since(p.since) >= self.tapping_term);
+        if held {
+            try!(self.hold(now, keyboard));
+            try!(self.synchronize());
+        }
+        let reload = self.last_reload
+            .map_or(true, |last| now.duration_since(last) >= Duration::from_secs(RELOAD_INTERVAL));
+        if reload {
+            self.last_reload = Some(now);
+            if self.reload() {
+                try!(self.light_layers(keyboard));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for UinputHandler {
+    fn drop(&mut self) {
+        if let Err(e) = self.release_all() {
+            self.report(e);
+        }
+    }
+}
+
+impl From<UinputHandler> for Handler {
+    fn from(handler: UinputHandler) -> Handler {
+        backend::into_handler(handler)
+    }
+}
+
+trait ToUinputKey {
+    fn to_uinput_key(&self) -> Option<UinputKey>;
+}
+
+impl ToUinputKey for MediaKey {
+    fn to_uinput_key(&self) -> Option<UinputKey> {
+        match self {
+            &MediaKey::None => None,
+            &MediaKey::Forward => Some(UinputKey::Misc(Misc::NextSong)),
+            &MediaKey::Backward => Some(UinputKey::Misc(Misc::PreviousSong)),
+            &MediaKey::Stop => Some(UinputKey::Misc(Misc::StopCD)),
+            &MediaKey::PlayPause => Some(UinputKey::Misc(Misc::PlayPause)),
+            &MediaKey::VolumeUp => Some(UinputKey::Misc(Misc::VolumeUp)),
+            &MediaKey::VolumeDown => Some(UinputKey::Misc(Misc::VolumeDown)),
+            &MediaKey::Mute => Some(UinputKey::Misc(Misc::Mute))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::process;
+    use std::rc::Rc;
+    use std::time::Duration;
+    use g910::*;
+    use uinput::event::{Keyboard as UinputKey};
+    use uinput::event::keyboard::{Key as UinputStandardKey, Misc};
+
+    use backend::Leds;
+    use config::Layer;
+    use error;
+    use mock::{Harness, MockSink, SinkEvent};
+    use super::{KeySink, Lock, LockLeds, UinputHandler};
+
+    fn key(key: UinputStandardKey) -> UinputKey {
+        UinputKey::Key(key)
+    }
+
+    #[test]
+    fn emits_remapped_keys() {
+        let sink = MockSink::new();
+        let mut harness = Harness::new(UinputHandler::with_sink(sink.clone())
+            .remap(StandardKey::CapsLock, StandardKey::Esc));
+        harness.click(Key::Standard(StandardKey::A)).unwrap();
+        harness.click(Key::Standard(StandardKey::CapsLock)).unwrap();
+        assert_eq!(sink.events(), vec![
+            SinkEvent::Press(key(UinputStandardKey::A)), SinkEvent::Synchronize,
+            SinkEvent::Release(key(UinputStandardKey::A)), SinkEvent::Synchronize,
+            SinkEvent::Press(key(UinputStandardKey::Esc)), SinkEvent::Synchronize,
+            SinkEvent::Release(key(UinputStandardKey::Esc)), SinkEvent::Synchronize,
+        ]);
+    }
+
+    #[test]
+    fn emits_function_keys_for_gaming_keys() {
+        let sink = MockSink::new();
+        let mut harness = Harness::new(UinputHandler::with_sink(sink.clone()));
+        let keys = [
+            (GamingKey::G1, Misc::F13), (GamingKey::G2, Misc::F14), (GamingKey::G3, Misc::F15),
+            (GamingKey::G4, Misc::F16), (GamingKey::G5, Misc::F17), (GamingKey::G6, Misc::F18),
+            (GamingKey::G7, Misc::F19), (GamingKey::G8, Misc::F20), (GamingKey::G9, Misc::F21),
+            (GamingKey::M1, Misc::F22), (GamingKey::M2, Misc::F23), (GamingKey::M3, Misc::F24),
+        ];
+        for &(gaming, misc) in &keys {
+            sink.clear();
+            harness.click(Key::Gaming(gaming)).unwrap();
+            assert_eq!(sink.events(), vec![
+                SinkEvent::Press(UinputKey::Misc(misc)), SinkEvent::Synchronize,
+                SinkEvent::Release(UinputKey::Misc(misc)), SinkEvent::Synchronize,
+            ], "{:?}", gaming);
+        }
+    }
+
+    #[test]
+    fn emits_configured_keys_for_gaming_keys() {
+        let sink = MockSink::new();
+        let mut harness = Harness::new(UinputHandler::with_sink(sink.clone())
+            .gaming(GamingKey::G1, vec![key(UinputStandardKey::LeftControl), key(UinputStandardKey::C)]));
+        harness.click(Key::Gaming(GamingKey::G1)).unwrap();
+        let events = sink.events();
+        assert!(events.contains(&SinkEvent::Press(key(UinputStandardKey::LeftControl))));
+        assert!(events.contains(&SinkEvent::Press(key(UinputStandardKey::C))));
+        assert!(!events.contains(&SinkEvent::Press(UinputKey::Misc(Misc::F13))));
+        sink.clear();
+        // the others keep their defaults
+        harness.click(Key::Gaming(GamingKey::G2)).unwrap();
+        assert_eq!(sink.events()[0], SinkEvent::Press(UinputKey::Misc(Misc::F14)));
+    }
+
+    #[test]
+    fn reloads_changed_config() {
+        let path = env::temp_dir().join(format!("uinput-config-test-{}", process::id()));
+        File::create(&path).unwrap().write_all(b"[remap]\nCapsLock = \"Esc\"\n").unwrap();
+        let sink = MockSink::new();
+        let mut harness = Harness::new(UinputHandler::with_sink(sink.clone()).config(&path).unwrap());
+        let caps = Key::Standard(StandardKey::CapsLock);
+        harness.click(caps.clone()).unwrap();
+        assert_eq!(sink.events()[0], SinkEvent::Press(key(UinputStandardKey::Esc)));
+        File::create(&path).unwrap().write_all(b"[remap]\nCapsLock = \"Tab\"\n").unwrap();
+        // as if the last check was before the write, whatever the resolution of the mtime
+        harness.handler_mut().config.as_mut().unwrap().modified = None;
+        harness.advance(Duration::from_secs(1)).unwrap();
+        sink.clear();
+        harness.click(caps).unwrap();
+        assert_eq!(sink.events()[0], SinkEvent::Press(key(UinputStandardKey::Tab)));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn releases_held_keys() {
+        let sink = MockSink::new();
+        let mut harness = Harness::new(UinputHandler::with_sink(sink.clone()));
+        harness.press(Key::Standard(StandardKey::LeftShift)).unwrap();
+        harness.press(Key::Standard(StandardKey::A)).unwrap();
+        sink.clear();
+        harness.handler_mut().release_all().unwrap();
+        assert_eq!(sink.events(), vec![
+            SinkEvent::Release(key(UinputStandardKey::A)),
+            SinkEvent::Release(key(UinputStandardKey::LeftShift)),
+            SinkEvent::Synchronize,
+        ]);
+    }
+
+    #[test]
+    fn restores_colors_of_layers() {
+        let sink = MockSink::new();
+        let handler = UinputHandler::with_sink(sink.clone())
+            .layer(Layer::new("nav").bind(StandardKey::H, vec![key(UinputStandardKey::Left)]))
+            .momentary(StandardKey::RightAlt, "nav").unwrap();
+        let mut harness = Harness::new(handler);
+        let (h, alt) = (Key::Standard(StandardKey::H), Key::Standard(StandardKey::RightAlt));
+        harness.keyboard_mut().set_key_colors(vec![KeyColor::new(h.clone(), Color::new(0, 0, 255))]).unwrap();
+        harness.press(alt.clone()).unwrap();
+        assert_eq!(harness.keyboard().color(&h), Some(Color::new(0, 255, 0)));
+        harness.click(h.clone()).unwrap();
+        harness.release(alt).unwrap();
+        assert_eq!(harness.keyboard().color(&h), Some(Color::new(0, 0, 255)));
+        assert_eq!(sink.events(), vec![
+            SinkEvent::Press(key(UinputStandardKey::Left)), SinkEvent::Synchronize,
+            SinkEvent::Release(key(UinputStandardKey::Left)), SinkEvent::Synchronize,
+        ]);
+    }
+
+    #[test]
+    fn forgets_colors_of_layers_when_releasing_all() {
+        let handler = UinputHandler::with_sink(MockSink::new())
+            .layer(Layer::new("nav").bind(StandardKey::H, vec![key(UinputStandardKey::Left)]))
+            .momentary(StandardKey::RightAlt, "nav").unwrap();
+        let mut harness = Harness::new(handler);
+        let (h, alt) = (Key::Standard(StandardKey::H), Key::Standard(StandardKey::RightAlt));
+        harness.keyboard_mut().set_key_colors(vec![KeyColor::new(h.clone(), Color::new(0, 0, 255))]).unwrap();
+        harness.press(alt.clone()).unwrap();
+        harness.handler_mut().release_all().unwrap();
+        // e.g. another profile drawing on the keyboard
+        harness.keyboard_mut().set_key_colors(vec![KeyColor::new(h.clone(), Color::new(255, 0, 0))]).unwrap();
+        harness.release(alt.clone()).unwrap();
+        harness.press(alt.clone()).unwrap();
+        assert_eq!(harness.keyboard().color(&h), Some(Color::new(0, 255, 0)));
+        harness.release(alt).unwrap();
+        assert_eq!(harness.keyboard().color(&h), Some(Color::new(255, 0, 0)));
+    }
+
+    #[test]
+    fn records_and_plays_macros() {
+        let path = env::temp_dir().join(format!("macros-test-{}", process::id()));
+        let _ = fs::remove_file(&path);
+        let sink = MockSink::new();
+        let mut harness = Harness::new(UinputHandler::with_sink(sink.clone()).macros(&path).unwrap());
+        let (g1, mr, a) = (Key::Gaming(GamingKey::G1), Key::Gaming(GamingKey::MR), Key::Standard(StandardKey::A));
+        harness.keyboard_mut().set_key_colors(vec![KeyColor::new(g1.clone(), Color::new(0, 0, 255))]).unwrap();
+        harness.click(mr.clone()).unwrap();
+        harness.click(g1.clone()).unwrap();
+        assert_eq!(harness.keyboard().color(&g1), Some(Color::new(255, 0, 0)));
+        harness.click(a).unwrap();
+        harness.click(mr).unwrap();
+        assert_eq!(harness.keyboard().color(&g1), Some(Color::new(0, 0, 255)));
+        sink.clear();
+        harness.click(g1).unwrap();
+        assert_eq!(sink.events(), vec![
+            SinkEvent::Press(key(UinputStandardKey::A)), SinkEvent::Synchronize,
+            SinkEvent::Release(key(UinputStandardKey::A)), SinkEvent::Synchronize,
+        ]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn mirrors_lock_leds() {
+        let sink = MockSink::new();
+        let mut harness = Harness::new(UinputHandler::with_sink(sink.clone())
+            .lock_leds(LockLeds::new(Color::new(255, 255, 255))));
+        let caps = Key::Standard(StandardKey::CapsLock);
+        harness.keyboard_mut().set_key_colors(vec![KeyColor::new(caps.clone(), Color::new(0, 0, 255))]).unwrap();
+        sink.set_led(Lock::Caps, true);
+        harness.tick().unwrap();
+        assert_eq!(harness.keyboard().color(&caps), Some(Color::new(255, 255, 255)));
+        sink.set_led(Lock::Caps, false);
+        harness.tick().unwrap();
+        assert_eq!(harness.keyboard().color(&caps), Some(Color::new(0, 0, 255)));
+    }
+
+    /// A sink without LEDs.
+    struct Blind;
+
+    impl KeySink for Blind {
+        #[allow(unused_variables)]
+        fn press(&mut self, key: &UinputKey) -> error::Result<()> {
+            Ok(())
+        }
+
+        #[allow(unused_variables)]
+        fn release(&mut self, key: &UinputKey) -> error::Result<()> {
+            Ok(())
+        }
+
+        fn synchronize(&mut self) -> error::Result<()> {
+            Ok(())
+        }
+
+        #[allow(unused_variables)]
+        fn repeat(&mut self, key: &UinputKey) -> error::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reports_unreadable_leds_once() {
+        let errors = Rc::new(Cell::new(0));
+        let counter = errors.clone();
+        let mut harness = Harness::new(UinputHandler::with_sink(Blind)
+            .lock_leds(LockLeds::new(Color::new(255, 255, 255)))
+            .on_error(move |_| counter.set(counter.get() + 1)));
+        harness.tick().unwrap();
+        harness.tick().unwrap();
+        assert_eq!(errors.get(), 1);
+    }
+
+    #[test]
+    fn reports_failed_writes_and_releases_held_keys() {
+        let errors = Rc::new(Cell::new(0));
+        let counter = errors.clone();
+        let sink = MockSink::new();
+        let mut harness = Harness::new(UinputHandler::with_sink(sink.clone())
+            .on_error(move |e| match e {
+                error::Error::Io(_) => counter.set(counter.get() + 1),
+                e => panic!("unexpected error {}", e),
+            }));
+        harness.press(Key::Standard(StandardKey::LeftShift)).unwrap();
+        sink.clear();
+        sink.fail_presses(true);
+        assert!(harness.press(Key::Standard(StandardKey::A)).is_err());
+        assert_eq!(errors.get(), 1);
+        assert_eq!(sink.events(), vec![
+            SinkEvent::Release(key(UinputStandardKey::LeftShift)), SinkEvent::Synchronize,
+        ]);
+    }
+
+    fn dual(sink: &MockSink) -> Harness<UinputHandler> {
+        Harness::new(UinputHandler::with_sink(sink.clone())
+            .dual_role(StandardKey::CapsLock, key(UinputStandardKey::Esc), key(UinputStandardKey::LeftControl))
+            .dual_role(StandardKey::Space, key(UinputStandardKey::Space), key(UinputStandardKey::LeftAlt)))
+    }
+
+    fn tapped(key: UinputKey) -> Vec<SinkEvent> {
+        vec![SinkEvent::Press(key), SinkEvent::Synchronize, SinkEvent::Release(key), SinkEvent::Synchronize]
+    }
+
+    #[test]
+    fn taps_dual_role_key() {
+        let sink = MockSink::new();
+        let mut harness = dual(&sink);
+        harness.press(Key::Standard(StandardKey::CapsLock)).unwrap();
+        harness.advance(Duration::from_millis(150)).unwrap();
+        assert!(sink.events().is_empty());
+        harness.release(Key::Standard(StandardKey::CapsLock)).unwrap();
+        assert_eq!(sink.events(), tapped(key(UinputStandardKey::Esc)));
+    }
+
+    #[test]
+    fn holds_dual_role_key_after_tapping_term() {
+        let sink = MockSink::new();
+        let mut harness = dual(&sink);
+        harness.press(Key::Standard(StandardKey::CapsLock)).unwrap();
+        harness.advance(Duration::from_millis(190)).unwrap();
+        assert!(sink.events().is_empty());
+        harness.advance(Duration::from_millis(10)).unwrap();
+        harness.release(Key::Standard(StandardKey::CapsLock)).unwrap();
+        assert_eq!(sink.events(), vec![
+            SinkEvent::Press(key(UinputStandardKey::LeftControl)), SinkEvent::Synchronize,
+            SinkEvent::Release(key(UinputStandardKey::LeftControl)), SinkEvent::Synchronize,
+        ]);
+    }
+
+    #[test]
+    fn holds_dual_role_key_interrupted_by_another_key() {
+        let sink = MockSink::new();
+        let mut harness = dual(&sink);
+        harness.press(Key::Standard(StandardKey::CapsLock)).unwrap();
+        harness.click(Key::Standard(StandardKey::A)).unwrap();
+        harness.release(Key::Standard(StandardKey::CapsLock)).unwrap();
+        let mut expected = vec![SinkEvent::Press(key(UinputStandardKey::LeftControl)), SinkEvent::Synchronize];
+        expected.extend(tapped(key(UinputStandardKey::A)));
+        expected.extend(vec![SinkEvent::Release(key(UinputStandardKey::LeftControl)), SinkEvent::Synchronize]);
+        assert_eq!(sink.events(), expected);
+    }
+
+    #[test]
+    fn taps_dual_role_key_inside_another() {
+        let sink = MockSink::new();
+        let mut harness = dual(&sink);
+        harness.press(Key::Standard(StandardKey::CapsLock)).unwrap();
+        harness.click(Key::Standard(StandardKey::Space)).unwrap();
+        harness.release(Key::Standard(StandardKey::CapsLock)).unwrap();
+        harness.advance(Duration::from_millis(500)).unwrap();
+        let mut expected = vec![SinkEvent::Press(key(UinputStandardKey::LeftControl)), SinkEvent::Synchronize];
+        expected.extend(tapped(key(UinputStandardKey::Space)));
+        expected.extend(vec![SinkEvent::Release(key(UinputStandardKey::LeftControl)), SinkEvent::Synchronize]);
+        assert_eq!(sink.events(), expected);
+    }
+
+    fn repeated(key: UinputKey) -> Vec<SinkEvent> {
+        vec![SinkEvent::Repeat(key), SinkEvent::Synchronize]
+    }
+
+    #[test]
+    fn repeats_last_held_key() {
+        let sink = MockSink::new();
+        let mut harness = Harness::new(UinputHandler::with_sink(sink.clone())
+            .repeat(Duration::from_millis(500), 10));
+        harness.press(Key::Standard(StandardKey::A)).unwrap();
+        harness.advance(Duration::from_millis(490)).unwrap();
+        assert_eq!(sink.events(), vec![SinkEvent::Press(key(UinputStandardKey::A)), SinkEvent::Synchronize]);
+        sink.clear();
+        harness.advance(Duration::from_millis(10)).unwrap();
+        assert_eq!(sink.events(), repeated(key(UinputStandardKey::A)));
+        harness.advance(Duration::from_millis(100)).unwrap();
+        let mut expected = repeated(key(UinputStandardKey::A));
+        expected.extend(repeated(key(UinputStandardKey::A)));
+        assert_eq!(sink.events(), expected);
+        sink.clear();
+        harness.release(Key::Standard(StandardKey::A)).unwrap();
+        harness.advance(Duration::from_millis(1000)).unwrap();
+        assert_eq!(sink.events(), vec![SinkEvent::Release(key(UinputStandardKey::A)), SinkEvent::Synchronize]);
+    }
+
+    #[test]
+    fn overrides_repeat_of_single_keys() {
+        let sink = MockSink::new();
+        let mut harness = Harness::new(UinputHandler::with_sink(sink.clone())
+            .repeat(Duration::from_millis(500), 10)
+            .key_repeat(StandardKey::A, Some((Duration::from_millis(200), 50)))
+            .key_repeat(StandardKey::Esc, None));
+        harness.press(Key::Standard(StandardKey::A)).unwrap();
+        harness.advance(Duration::from_millis(220)).unwrap();
+        let mut expected = vec![SinkEvent::Press(key(UinputStandardKey::A)), SinkEvent::Synchronize];
+        expected.extend(repeated(key(UinputStandardKey::A)));
+        expected.extend(repeated(key(UinputStandardKey::A)));
+        assert_eq!(sink.events(), expected);
+        sink.clear();
+        // pressing a key without repeat stops repeating the previous one as well
+        harness.press(Key::Standard(StandardKey::Esc)).unwrap();
+        harness.advance(Duration::from_millis(1000)).unwrap();
+        assert_eq!(sink.events(), vec![SinkEvent::Press(key(UinputStandardKey::Esc)), SinkEvent::Synchronize]);
+    }
+}